@@ -0,0 +1,118 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{scene::Ray, shapes::Vector};
+
+/// Axis aligned bounding box
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Aabb {
+    pub min: Vector,
+    pub max: Vector,
+}
+impl Aabb {
+    /// Box that contains nothing, growing it with anything yields that thing's bounds
+    pub const EMPTY: Aabb = Aabb {
+        min: na::Vector3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        max: na::Vector3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+    };
+
+    pub fn new(a: Vector, b: Vector) -> Self {
+        Aabb {
+            min: a.inf(&b),
+            max: a.sup(&b),
+        }
+    }
+    /// Smallest box containing all the points
+    pub fn from_points<'p>(points: impl IntoIterator<Item = &'p Vector>) -> Self {
+        points
+            .into_iter()
+            .fold(Aabb::EMPTY, |aabb, point| aabb.grow(point))
+    }
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+    /// Smallest box containing both `self` and `point`
+    pub fn grow(&self, point: &Vector) -> Self {
+        Aabb {
+            min: self.min.inf(point),
+            max: self.max.sup(point),
+        }
+    }
+    /// Smallest box containing both `self` and `other`
+    pub fn union(&self, other: &Aabb) -> Self {
+        Aabb {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+    /// Grows the box by `margin` on every side
+    pub fn pad(&self, margin: f64) -> Self {
+        let margin = Vector::repeat(margin);
+        Aabb {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+    pub fn centroid(&self) -> Vector {
+        (self.min + self.max) * 0.5
+    }
+    pub fn extent(&self) -> Vector {
+        self.max - self.min
+    }
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.extent();
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+    pub fn contains(&self, point: &Vector) -> bool {
+        (0..3).all(|axis| point[axis] >= self.min[axis] && point[axis] <= self.max[axis])
+    }
+    /// Returns the eight corners of the box
+    pub fn corners(&self) -> [Vector; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vector::new(a.x, a.y, a.z),
+            Vector::new(b.x, a.y, a.z),
+            Vector::new(a.x, b.y, a.z),
+            Vector::new(b.x, b.y, a.z),
+            Vector::new(a.x, a.y, b.z),
+            Vector::new(b.x, a.y, b.z),
+            Vector::new(a.x, b.y, b.z),
+            Vector::new(b.x, b.y, b.z),
+        ]
+    }
+    /// Whether the infinite line the ray lies on, behind its origin included, goes through the box
+    pub fn crossed_by(&self, ray: &Ray, inv_direction: &Vector) -> bool {
+        let mut t_enter = f64::NEG_INFINITY;
+        let mut t_exit = f64::INFINITY;
+        for axis in 0..3 {
+            let t1 = (self.min[axis] - ray.origin[axis]) * inv_direction[axis];
+            let t2 = (self.max[axis] - ray.origin[axis]) * inv_direction[axis];
+            t_enter = t_enter.max(t1.min(t2));
+            t_exit = t_exit.min(t1.max(t2));
+        }
+        t_enter <= t_exit
+    }
+    /// Distance along the ray where it enters the box, if it does so before `t_max`.
+    /// `inv_direction` is the component-wise inverse of the ray's direction.
+    pub fn hit(&self, ray: &Ray, inv_direction: &Vector, t_max: f64) -> Option<f64> {
+        // Slab method: intersect the ranges in which the ray is between each pair of planes
+        let mut t_enter = 0.0_f64;
+        let mut t_exit = t_max;
+        for axis in 0..3 {
+            let t1 = (self.min[axis] - ray.origin[axis]) * inv_direction[axis];
+            let t2 = (self.max[axis] - ray.origin[axis]) * inv_direction[axis];
+            // `NaN`s (ray parallel and on the slab) are ignored by `min`/`max`
+            t_enter = t_enter.max(t1.min(t2));
+            t_exit = t_exit.min(t1.max(t2));
+        }
+        if t_enter <= t_exit {
+            Some(t_enter)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{
+    accel::Aabb,
+    scene::Ray,
+    shapes::{Hit, Vector},
+};
+
+/// Most primitives a leaf may hold before being split
+const MAX_LEAF_SIZE: usize = 4;
+/// Amount of buckets used to estimate the best split
+const BINS: usize = 12;
+/// Cost of testing a node's box relative to testing a primitive
+const TRAVERSAL_COST: f64 = 0.5;
+
+#[derive(Clone, Debug)]
+enum Node {
+    /// Primitives `indices[start..start + count]`
+    Leaf {
+        bounds: Aabb,
+        start: usize,
+        count: usize,
+    },
+    /// The left child is always the next node, the right one is at `right`
+    Inner { bounds: Aabb, right: usize },
+}
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Inner { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// Bounding volume hierarchy over a list of primitives, built with the surface area heuristic.
+///
+/// It only stores the boxes and the primitives' indices, testing the primitives
+/// themselves is left to the caller.
+#[derive(Clone, Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+impl Bvh {
+    /// Builds a hierarchy where primitive `i` is bounded by `bounds[i]`
+    pub fn build(bounds: &[Aabb]) -> Self {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(bounds.len() * 2),
+            indices: (0..bounds.len()).collect(),
+        };
+        if !bounds.is_empty() {
+            let centroids: Vec<Vector> = bounds.iter().map(Aabb::centroid).collect();
+            bvh.split(bounds, &centroids, 0, bounds.len());
+        }
+        bvh
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map(|node| *node.bounds())
+            .unwrap_or(Aabb::EMPTY)
+    }
+
+    /// Recursively adds the node holding `indices[start..end]`
+    fn split(&mut self, bounds: &[Aabb], centroids: &[Vector], start: usize, end: usize) {
+        let node_bounds = self.indices[start..end]
+            .iter()
+            .fold(Aabb::EMPTY, |aabb, &i| aabb.union(&bounds[i]));
+        let count = end - start;
+        let leaf = Node::Leaf {
+            bounds: node_bounds,
+            start,
+            count,
+        };
+        if count <= MAX_LEAF_SIZE {
+            self.nodes.push(leaf);
+            return;
+        }
+
+        let centroid_bounds =
+            Aabb::from_points(self.indices[start..end].iter().map(|&i| &centroids[i]));
+        let extent = centroid_bounds.extent();
+        let axis = extent.imax();
+        let longest = extent[axis];
+        if float!(longest -> 0) {
+            // Everything is stacked on the same spot, there is nothing to split
+            self.nodes.push(leaf);
+            return;
+        }
+
+        // Sort the primitives into buckets along the axis and find the cheapest boundary
+        let bin_of = |i: usize| {
+            let offset = (centroids[i][axis] - centroid_bounds.min[axis]) / extent[axis];
+            ((offset * BINS as f64) as usize).min(BINS - 1)
+        };
+        let mut bins = [(Aabb::EMPTY, 0_usize); BINS];
+        for &i in &self.indices[start..end] {
+            let bin = &mut bins[bin_of(i)];
+            bin.0 = bin.0.union(&bounds[i]);
+            bin.1 += 1;
+        }
+        let (best_split, best_cost) = (1..BINS)
+            .map(|split| {
+                let side = |bins: &[(Aabb, usize)]| {
+                    bins.iter()
+                        .fold((Aabb::EMPTY, 0), |(aabb, n), (bin, count)| {
+                            (aabb.union(bin), n + count)
+                        })
+                };
+                let (left, n_left) = side(&bins[..split]);
+                let (right, n_right) = side(&bins[split..]);
+                let cost = TRAVERSAL_COST
+                    + (left.surface_area() * n_left as f64 + right.surface_area() * n_right as f64)
+                        / node_bounds.surface_area();
+                (split, cost)
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap();
+        if best_cost >= count as f64 && count <= MAX_LEAF_SIZE * 4 {
+            // Splitting wouldn't pay off
+            self.nodes.push(leaf);
+            return;
+        }
+
+        let mid = start + partition(&mut self.indices[start..end], |&i| bin_of(i) < best_split);
+        let mid = if mid == start || mid == end {
+            // Degenerate split, fall back to halving the list
+            start + count / 2
+        } else {
+            mid
+        };
+
+        let this = self.nodes.len();
+        self.nodes.push(Node::Inner {
+            bounds: node_bounds,
+            right: 0,
+        });
+        self.split(bounds, centroids, start, mid);
+        let right_child = self.nodes.len();
+        if let Node::Inner { right, .. } = &mut self.nodes[this] {
+            *right = right_child;
+        }
+        self.split(bounds, centroids, mid, end);
+    }
+
+    /// Finds the closest primitive hit by `ray` before `t_max`.
+    ///
+    /// `hit` is called with the index of every primitive whose box the ray goes through,
+    /// and returns where the ray hits it, if it does.
+    pub fn closest(
+        &self,
+        ray: &Ray,
+        t_max: f64,
+        mut hit: impl FnMut(usize) -> Option<Hit>,
+    ) -> Option<(Hit, usize)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inv_direction = ray.direction.map(|d| 1.0 / d);
+        let mut closest: Option<(Hit, usize)> = None;
+        let mut t_max = t_max;
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+        while let Some(node) = stack.pop() {
+            match self.nodes[node] {
+                Node::Leaf {
+                    ref bounds,
+                    start,
+                    count,
+                } => {
+                    if bounds.hit(ray, &inv_direction, t_max).is_none() {
+                        continue;
+                    }
+                    for &i in &self.indices[start..start + count] {
+                        if let Some(hit) = hit(i) {
+                            if hit.t < t_max {
+                                t_max = hit.t;
+                                closest = Some((hit, i));
+                            }
+                        }
+                    }
+                }
+                Node::Inner { ref bounds, right } => {
+                    if bounds.hit(ray, &inv_direction, t_max).is_none() {
+                        continue;
+                    }
+                    let left = node + 1;
+                    let t_left = self.nodes[left].bounds().hit(ray, &inv_direction, t_max);
+                    let t_right = self.nodes[right].bounds().hit(ray, &inv_direction, t_max);
+                    // Push the farthest child first so the nearest one is visited first
+                    match (t_left, t_right) {
+                        (Some(l), Some(r)) if l <= r => stack.extend([right, left]),
+                        (Some(_), Some(_)) => stack.extend([left, right]),
+                        (Some(_), None) => stack.push(left),
+                        (None, Some(_)) => stack.push(right),
+                        (None, None) => {}
+                    }
+                }
+            }
+        }
+        closest
+    }
+
+    /// Calls `visit` with every primitive whose box the line the ray lies on goes through,
+    /// in both directions
+    pub fn query_line(&self, ray: &Ray, mut visit: impl FnMut(usize)) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let inv_direction = ray.direction.map(|d| 1.0 / d);
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            if !self.nodes[node].bounds().crossed_by(ray, &inv_direction) {
+                continue;
+            }
+            match self.nodes[node] {
+                Node::Leaf { start, count, .. } => self.indices[start..start + count]
+                    .iter()
+                    .for_each(|&i| visit(i)),
+                Node::Inner { right, .. } => stack.extend([right, node + 1]),
+            }
+        }
+    }
+
+    /// Calls `visit` with every primitive whose box contains `point`
+    pub fn query_point(&self, point: &Vector, mut visit: impl FnMut(usize)) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            if !self.nodes[node].bounds().contains(point) {
+                continue;
+            }
+            match self.nodes[node] {
+                Node::Leaf { start, count, .. } => self.indices[start..start + count]
+                    .iter()
+                    .for_each(|&i| visit(i)),
+                Node::Inner { right, .. } => stack.extend([right, node + 1]),
+            }
+        }
+    }
+}
+
+/// Moves the elements matching `pred` to the front, returning how many there are
+fn partition<T>(items: &mut [T], pred: impl Fn(&T) -> bool) -> usize {
+    let mut split = 0;
+    for i in 0..items.len() {
+        if pred(&items[i]) {
+            items.swap(split, i);
+            split += 1;
+        }
+    }
+    split
+}
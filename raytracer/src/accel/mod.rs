@@ -0,0 +1,78 @@
+use crate::{
+    scene::Ray,
+    shapes::{Hit, Object, Shape},
+};
+
+mod aabb;
+mod bvh;
+
+pub use aabb::Aabb;
+pub use bvh::Bvh;
+
+/// Speeds up finding what a ray hits among a list of objects.
+///
+/// Bounded objects go in a [`Bvh`], unbounded ones like planes are kept aside
+/// and tested against every ray.
+#[derive(Clone, Debug, Default)]
+pub struct Accel {
+    bvh: Bvh,
+    /// Index of the object for each primitive in the BVH
+    bounded: Vec<usize>,
+    unbounded: Vec<usize>,
+}
+impl Accel {
+    pub fn build(objects: &[Shape]) -> Self {
+        let mut bounds = Vec::new();
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        for (i, obj) in objects.iter().enumerate() {
+            match obj.bounds() {
+                Some(aabb) => {
+                    bounds.push(aabb);
+                    bounded.push(i);
+                }
+                None => unbounded.push(i),
+            }
+        }
+        Accel {
+            bvh: Bvh::build(&bounds),
+            bounded,
+            unbounded,
+        }
+    }
+
+    /// Box around every bounded object, planes and other unbounded objects are left out
+    pub fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+
+    /// Finds the closest of `objects` hit by `ray` before `t_max`, returning its index.
+    /// `objects` must be the same list the structure was built with.
+    pub fn closest(&self, ray: &Ray, t_max: f64, objects: &[Shape]) -> Option<(Hit, usize)> {
+        let hit = |i: usize| {
+            let hit = objects[i].hit(ray)?;
+            let t = hit.t;
+            debug_assert!(t.is_finite(), "hit produced an inf");
+            debug_assert!(float!(t > 0), "hit produced a negative {t}");
+            // Don't collide with self
+            if float!(t -> 0) {
+                None
+            } else {
+                Some(hit)
+            }
+        };
+
+        let mut closest = self
+            .bvh
+            .closest(ray, t_max, |i| hit(self.bounded[i]))
+            .map(|(hit, i)| (hit, self.bounded[i]));
+        for &i in &self.unbounded {
+            if let Some(hit) = hit(i) {
+                if hit.t < closest.map_or(t_max, |(closest, _)| closest.t) {
+                    closest = Some((hit, i));
+                }
+            }
+        }
+        closest
+    }
+}
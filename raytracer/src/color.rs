@@ -1,4 +1,6 @@
-use image::Rgb;
+use std::{fmt::Display, str::FromStr};
+
+use image::{Pixel, Rgb};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
@@ -10,3 +12,71 @@ pub const MAGENTA: Color = Rgb([1.0, 0.0, 1.0]);
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "serde", serde(remote = "Rgb::<f32>"))]
 pub(crate) struct RgbDef([f32; 3]);
+
+/// Encodes a linear channel with the sRGB transfer function used by 8 bit images and screens
+pub fn linear_to_srgb(channel: f32) -> f32 {
+    if channel <= 0.003_130_8 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Squeezes the unbounded light of a render into the 0 to 1 range a screen can show
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Tonemap {
+    /// Cuts off everything brighter than white
+    #[default]
+    Clamp,
+    /// `x / (1 + x)`, never quite reaches white
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve, with a soft shoulder and more contrast
+    Aces,
+}
+impl Tonemap {
+    const ALL: [Tonemap; 3] = [Tonemap::Clamp, Tonemap::Reinhard, Tonemap::Aces];
+
+    /// Maps linear light to linear light between 0 and 1
+    pub fn apply(self, color: Color) -> Color {
+        color.map(|c| {
+            let c = c.max(0.0);
+            match self {
+                Tonemap::Clamp => c.min(1.0),
+                Tonemap::Reinhard => c / (1.0 + c),
+                Tonemap::Aces => {
+                    ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).clamp(0.0, 1.0)
+                }
+            }
+        })
+    }
+}
+impl FromStr for Tonemap {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Tonemap::ALL
+            .into_iter()
+            .find(|tonemap| tonemap.to_string() == s)
+            .ok_or_else(|| {
+                let names: Vec<String> = Tonemap::ALL.iter().map(|t| t.to_string()).collect();
+                format!(
+                    "unknown tonemap `{s}`, expected one of: {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+impl Display for Tonemap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Tonemap::Clamp => "clamp",
+            Tonemap::Reinhard => "reinhard",
+            Tonemap::Aces => "aces",
+        };
+        write!(f, "{name}")
+    }
+}
@@ -0,0 +1,163 @@
+use image::{Pixel, Rgb, Rgb32FImage};
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{color::Color, scene::RenderTarget};
+
+/// Weights of the B3 spline the filter is built from
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+/// Smallest albedo divided out of the beauty image, keeps black surfaces from blowing up
+const MIN_ALBEDO: f32 = 1e-3;
+
+/// Edge-avoiding à-trous wavelet filter, following Dammertz et al.
+/// "Edge-Avoiding À-Trous Wavelet Transform for fast Global Illumination Filtering".
+///
+/// The lighting is separated from the surface colors, blurred with wider and wider
+/// sparse kernels that stop at edges found in the normal, albedo and depth buffers
+/// of a [`RenderTarget`], then multiplied by the surface colors again.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct Denoiser {
+    /// Passes of the filter, every pass doubles the distance between the taps
+    pub iterations: u32,
+    /// How different two colors may be and still be blended, halved every pass
+    pub sigma_color: f32,
+    /// How different two normals may be, as the distance between the unit vectors
+    pub sigma_normal: f32,
+    /// How different two albedos may be
+    pub sigma_albedo: f32,
+    /// How different two depths may be, relative to the farthest one
+    pub sigma_depth: f32,
+}
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            iterations: 5,
+            sigma_color: 1.0,
+            sigma_normal: 0.3,
+            sigma_albedo: 0.1,
+            sigma_depth: 0.05,
+        }
+    }
+}
+impl Denoiser {
+    /// Filtered copy of the beauty image of `target`, using every core
+    pub fn apply(&self, target: &RenderTarget) -> Rgb32FImage {
+        let (w, h) = target.dimensions();
+        let mut irradiance = target.beauty.clone();
+        for (pixel, albedo) in irradiance.pixels_mut().zip(target.albedo.pixels()) {
+            *pixel = pixel.map2(albedo, |c, a| c / a.max(MIN_ALBEDO));
+        }
+
+        let mut scratch = Rgb32FImage::new(w, h);
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration.min(16);
+            let sigma_color = self.sigma_color * 0.5f32.powi(iteration as i32);
+            let input = &irradiance;
+            scratch
+                .par_chunks_mut(3 * w as usize)
+                .enumerate()
+                .for_each(|(y, row)| {
+                    for (x, out) in row.chunks_mut(3).enumerate() {
+                        let filtered = self.filter_pixel(
+                            input,
+                            target,
+                            (x as u32, y as u32),
+                            step,
+                            sigma_color,
+                        );
+                        out.copy_from_slice(&filtered.0);
+                    }
+                });
+            std::mem::swap(&mut irradiance, &mut scratch);
+        }
+
+        for (pixel, albedo) in irradiance.pixels_mut().zip(target.albedo.pixels()) {
+            *pixel = pixel.map2(albedo, |c, a| c * a.max(MIN_ALBEDO));
+        }
+        irradiance
+    }
+
+    /// Weighted average of the 5x5 taps around `(x, y)`, `step` pixels apart
+    fn filter_pixel(
+        &self,
+        input: &Rgb32FImage,
+        target: &RenderTarget,
+        (x, y): (u32, u32),
+        step: i64,
+        sigma_color: f32,
+    ) -> Color {
+        let (w, h) = input.dimensions();
+        let center = Guides::at(input, target, x, y);
+        let mut sum = [0.0; 3];
+        let mut total = 0.0;
+        for (j, ky) in KERNEL.iter().enumerate() {
+            let qy = y as i64 + (j as i64 - 2) * step;
+            if qy < 0 || qy >= h as i64 {
+                continue;
+            }
+            for (i, kx) in KERNEL.iter().enumerate() {
+                let qx = x as i64 + (i as i64 - 2) * step;
+                if qx < 0 || qx >= w as i64 {
+                    continue;
+                }
+                let tap = Guides::at(input, target, qx as u32, qy as u32);
+                let weight = kx * ky * self.edge_weight(&center, &tap, sigma_color);
+                for (sum, c) in sum.iter_mut().zip(tap.color.0) {
+                    *sum += c * weight;
+                }
+                total += weight;
+            }
+        }
+        // The center tap always has a weight, so `total` is never zero
+        Rgb(sum.map(|c| c / total))
+    }
+
+    /// How much a neighbour should count, 1 if it looks like the same surface
+    fn edge_weight(&self, center: &Guides, tap: &Guides, sigma_color: f32) -> f32 {
+        // Compressed so that a few very bright samples don't stop all blending
+        let color = distance_squared(compress(center.color), compress(tap.color));
+        let normal = distance_squared(center.normal, tap.normal);
+        let albedo = distance_squared(center.albedo, tap.albedo);
+        let depth = match (center.depth.is_finite(), tap.depth.is_finite()) {
+            (true, true) => {
+                let far = center.depth.max(tap.depth).max(f32::EPSILON);
+                ((center.depth - tap.depth) / far).powi(2)
+            }
+            (false, false) => 0.0,
+            _ => return 0.0,
+        };
+        (-color / sigma_color.powi(2).max(f32::EPSILON)
+            - normal / self.sigma_normal.powi(2).max(f32::EPSILON)
+            - albedo / self.sigma_albedo.powi(2).max(f32::EPSILON)
+            - depth / self.sigma_depth.powi(2).max(f32::EPSILON))
+        .exp()
+    }
+}
+
+/// Everything the filter compares between two pixels
+struct Guides {
+    color: Color,
+    normal: Color,
+    albedo: Color,
+    depth: f32,
+}
+impl Guides {
+    fn at(input: &Rgb32FImage, target: &RenderTarget, x: u32, y: u32) -> Self {
+        Guides {
+            color: *input.get_pixel(x, y),
+            normal: *target.normal.get_pixel(x, y),
+            albedo: *target.albedo.get_pixel(x, y),
+            depth: target.depth.get_pixel(x, y).0[0],
+        }
+    }
+}
+
+fn compress(color: Color) -> Color {
+    color.map(|c| c / (1.0 + c.max(0.0)))
+}
+
+fn distance_squared(a: Color, b: Color) -> f32 {
+    a.0.iter().zip(b.0).map(|(a, b)| (a - b).powi(2)).sum()
+}
@@ -0,0 +1,129 @@
+use image::Rgb;
+
+use crate::{
+    color::{Color, BLACK},
+    integrator::{FirstHit, Integrator},
+    sampling::Sampler,
+    scene::{Ray, Scene},
+    shapes::Object,
+};
+
+/// Colors the first hit along `ray` with `shade`, or with `miss` if nothing was hit
+fn shade_first_hit(
+    ray: &Ray,
+    scene: &Scene,
+    miss: Color,
+    shade: impl FnOnce(&FirstHit) -> Color,
+) -> (Color, Option<FirstHit>) {
+    let first_hit = FirstHit::trace(ray, scene);
+    (first_hit.as_ref().map_or(miss, shade), first_hit)
+}
+
+/// Shows the surface normals, with each axis going from 0 to 1 in a color channel
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Normals;
+impl Integrator for Normals {
+    fn li(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        self.li_with_first_hit(ray, scene, sampler).0
+    }
+    fn li_with_first_hit(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        _sampler: &mut dyn Sampler,
+    ) -> (Color, Option<FirstHit>) {
+        shade_first_hit(ray, scene, BLACK, |first| {
+            let normal = first.normal.map(|c| (c * 0.5 + 0.5) as f32);
+            Rgb([normal.x, normal.y, normal.z])
+        })
+    }
+}
+
+/// Shows how far surfaces are from the camera, white at `near` fading to black at `far`
+#[derive(Clone, Copy, Debug)]
+pub struct Depth {
+    pub near: f64,
+    pub far: f64,
+}
+impl Depth {
+    /// Spreads the gradient over the box around the scene's bounded objects
+    pub fn fit(scene: &Scene) -> Self {
+        let bounds = scene.accel().bounds();
+        if bounds.is_empty() {
+            return Depth {
+                near: 0.0,
+                far: 10.0,
+            };
+        }
+        let origin = scene.camera.origin();
+        let far = bounds
+            .corners()
+            .iter()
+            .map(|corner| (corner - origin).norm())
+            .fold(0.0, f64::max);
+        // Closest point of the box, the origin itself if it's inside
+        let near = (origin - origin.sup(&bounds.min).inf(&bounds.max)).norm();
+        Depth { near, far }
+    }
+}
+impl Integrator for Depth {
+    fn li(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        self.li_with_first_hit(ray, scene, sampler).0
+    }
+    fn li_with_first_hit(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        _sampler: &mut dyn Sampler,
+    ) -> (Color, Option<FirstHit>) {
+        shade_first_hit(ray, scene, BLACK, |first| {
+            let distance = first.hit.t * ray.direction.norm();
+            let depth = (distance - self.near) / (self.far - self.near).max(f64::EPSILON);
+            Rgb([(1.0 - depth).clamp(0.0, 1.0) as f32; 3])
+        })
+    }
+}
+
+/// Shows the color of every surface without any lighting
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Albedo;
+impl Integrator for Albedo {
+    fn li(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        self.li_with_first_hit(ray, scene, sampler).0
+    }
+    fn li_with_first_hit(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        _sampler: &mut dyn Sampler,
+    ) -> (Color, Option<FirstHit>) {
+        shade_first_hit(ray, scene, scene.background, |first| {
+            let obj = &scene.shapes()[first.index];
+            scene.materials.get(obj.material()).albedo
+        })
+    }
+}
+
+/// Paints every object with a color derived from its index in [`Scene::shapes`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ObjectId;
+impl Integrator for ObjectId {
+    fn li(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        self.li_with_first_hit(ray, scene, sampler).0
+    }
+    fn li_with_first_hit(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        _sampler: &mut dyn Sampler,
+    ) -> (Color, Option<FirstHit>) {
+        shade_first_hit(ray, scene, BLACK, |first| id_color(first.index))
+    }
+}
+
+fn id_color(index: usize) -> Color {
+    // Scramble the bits so that neighbouring indices get unrelated colors
+    let hash = (index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    let channel = |shift: u32| ((hash >> shift) & 0xff) as f32 / 255.0;
+    Rgb([channel(40), channel(48), channel(56)])
+}
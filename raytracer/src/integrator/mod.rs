@@ -0,0 +1,173 @@
+use std::{fmt::Display, str::FromStr};
+
+use image::Pixel;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    color::Color,
+    sampling::Sampler,
+    scene::{Incident, Ray, Scene},
+    shapes::{Hit, Object, Vector},
+};
+
+mod debug;
+mod path;
+mod whitted;
+
+pub use debug::{Albedo, Depth, Normals, ObjectId};
+pub use path::PathTracer;
+pub use whitted::Whitted;
+
+/// Shading model, finds how much light travels back along a camera ray.
+///
+/// Implement it to add new ways of rendering a [`Scene`] and pass it to
+/// [`render_with`](crate::scene::render_with).
+pub trait Integrator: Send + Sync {
+    /// Light arriving at the ray's origin from the direction it points to.
+    /// Random decisions must use `sampler` so that samplers can spread them evenly.
+    fn li(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color;
+    /// Like [`Integrator::li`], also giving what the ray hit first, for the buffers of a
+    /// [`RenderTarget`](crate::scene::RenderTarget). The default traces the ray a second time,
+    /// integrators should give what they found while shading instead.
+    fn li_with_first_hit(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+    ) -> (Color, Option<FirstHit>) {
+        (self.li(ray, scene, sampler), FirstHit::trace(ray, scene))
+    }
+}
+
+/// First surface hit by a camera ray, as found while shading it
+#[derive(Clone, Copy, Debug)]
+pub struct FirstHit {
+    pub hit: Hit,
+    /// Index of the object in [`Scene::shapes`]
+    pub index: usize,
+    /// World space unit normal
+    pub normal: Vector,
+    /// Fraction of the light from [`Scene::lights`] that isn't blocked, weighted by brightness.
+    /// `None` if the integrator didn't cast shadow rays from there.
+    pub visibility: Option<f32>,
+}
+impl FirstHit {
+    /// Finds what `ray` hits first, without casting shadow rays
+    pub fn trace(ray: &Ray, scene: &Scene) -> Option<Self> {
+        let (hit, index) = scene.closest_index(ray)?;
+        Some(FirstHit {
+            hit,
+            index,
+            normal: scene.shapes()[index].hit_normal(ray, &hit),
+            visibility: None,
+        })
+    }
+}
+
+/// Running total of the direct light reaching a point and how much of it isn't blocked,
+/// for [`FirstHit::visibility`]
+#[derive(Clone, Copy, Debug, Default)]
+struct Visibility {
+    lit: f32,
+    total: f32,
+}
+impl Visibility {
+    /// Counts `incident` light arriving at `cos` to the normal
+    fn add(&mut self, incident: &Incident, cos: f64, blocked: bool) {
+        let weight = incident.radiance.to_luma().0[0] * cos as f32;
+        self.total += weight;
+        if !blocked {
+            self.lit += weight;
+        }
+    }
+    /// Fully visible when no light reaches the point at all
+    fn fraction(self) -> f32 {
+        if self.total > 0.0 {
+            self.lit / self.total
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Integrators that can be picked from a scene file or the command line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum IntegratorKind {
+    /// Direct lighting with hard shadows plus perfect reflections and refractions. Fast
+    #[default]
+    Whitted,
+    /// Unbiased global illumination, emissive objects light the scene. Needs many samples
+    PathTracer,
+    /// Surface normals, debug view
+    Normals,
+    /// Distance to the camera, debug view
+    Depth,
+    /// Surface color without lighting, debug view
+    Albedo,
+    /// A different color for every object, debug view
+    ObjectId,
+}
+impl IntegratorKind {
+    const ALL: [IntegratorKind; 6] = [
+        IntegratorKind::Whitted,
+        IntegratorKind::PathTracer,
+        IntegratorKind::Normals,
+        IntegratorKind::Depth,
+        IntegratorKind::Albedo,
+        IntegratorKind::ObjectId,
+    ];
+
+    /// Creates the integrator, configured by the scene's settings
+    pub fn build(self, scene: &Scene) -> Box<dyn Integrator> {
+        let max_depth = scene.settings.max_depth;
+        match self {
+            IntegratorKind::Whitted => Box::new(Whitted { max_depth }),
+            IntegratorKind::PathTracer => Box::new(PathTracer { max_depth }),
+            IntegratorKind::Normals => Box::new(Normals),
+            IntegratorKind::Depth => Box::new(Depth::fit(scene)),
+            IntegratorKind::Albedo => Box::new(Albedo),
+            IntegratorKind::ObjectId => Box::new(ObjectId),
+        }
+    }
+}
+impl FromStr for IntegratorKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        IntegratorKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_string() == s)
+            .ok_or_else(|| {
+                let names: Vec<String> =
+                    IntegratorKind::ALL.iter().map(|k| k.to_string()).collect();
+                format!(
+                    "unknown integrator `{s}`, expected one of: {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+impl Display for IntegratorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            IntegratorKind::Whitted => "whitted",
+            IntegratorKind::PathTracer => "path_tracer",
+            IntegratorKind::Normals => "normals",
+            IntegratorKind::Depth => "depth",
+            IntegratorKind::Albedo => "albedo",
+            IntegratorKind::ObjectId => "object_id",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Schlick's approximation of the fraction of light that gets reflected
+fn schlick(cos: f64, eta: f64) -> f64 {
+    let r0 = ((1.0 - eta) / (1.0 + eta)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+}
@@ -0,0 +1,294 @@
+use std::f64::consts::PI;
+
+use crate::{
+    color::Color,
+    integrator::{schlick, FirstHit, Integrator, Visibility},
+    material::Material,
+    sampling::{cosine_hemisphere, Sampler},
+    scene::{Ray, Scene},
+    shapes::{Object, Vector},
+};
+
+/// Bounces after which paths may be randomly terminated
+const ROULETTE_START: u32 = 3;
+
+/// Unidirectional path tracer.
+///
+/// Diffuse bounces use cosine-weighted sampling and gather light with next-event estimation,
+/// combining light and BSDF samples of emissive objects with multiple importance sampling.
+/// Mirror and glass bounces are followed as perfectly specular.
+#[derive(Clone, Copy, Debug)]
+pub struct PathTracer {
+    /// Bounces after which paths are cut, Russian roulette usually ends them earlier
+    pub max_depth: u32,
+}
+impl Integrator for PathTracer {
+    fn li(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        path_trace(ray, scene, self.max_depth, sampler).0
+    }
+    fn li_with_first_hit(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+    ) -> (Color, Option<FirstHit>) {
+        path_trace(ray, scene, self.max_depth, sampler)
+    }
+}
+
+/// Light arriving along `ray` and what the ray hit first
+fn path_trace(
+    ray: &Ray,
+    scene: &Scene,
+    max_depth: u32,
+    sampler: &mut dyn Sampler,
+) -> (Color, Option<FirstHit>) {
+    let mut radiance = Vector::zeros();
+    let mut throughput = Vector::repeat(1.0);
+    let mut ray = Ray {
+        origin: ray.origin,
+        direction: ray.direction,
+    };
+    // Light directly seen by the camera or through mirrors can't be sampled
+    let mut specular_bounce = true;
+    let mut bsdf_pdf = 0.0;
+    let mut first_hit = None;
+
+    for bounce in 0..=max_depth {
+        let (hit, index) = match scene.closest_index(&ray) {
+            Some(closest) => closest,
+            None => {
+                radiance += throughput.component_mul(&environment(scene));
+                break;
+            }
+        };
+        let obj = &scene.shapes()[index];
+        let material = scene.materials.get(obj.material());
+        let t = hit.t;
+        let at = ray.at(t);
+        let normal = obj.hit_normal(&ray, &hit);
+        let facing = if ray.direction.dot(&normal) > 0.0 {
+            -normal
+        } else {
+            normal
+        };
+        if bounce == 0 {
+            first_hit = Some(FirstHit {
+                hit,
+                index,
+                normal,
+                visibility: None,
+            });
+        }
+
+        let emission = to_vector(material.emission);
+        if emission != Vector::zeros() {
+            let weight = match emitter_pdf(scene, index, &ray, t, &normal) {
+                Some(light_pdf) if !specular_bounce => power_heuristic(bsdf_pdf, light_pdf),
+                _ => 1.0,
+            };
+            radiance += throughput.component_mul(&emission) * weight;
+        }
+
+        // Pick one of the material's lobes with the probability of its weight
+        let albedo = to_vector(material.albedo);
+        let lobe = sampler.next_1d();
+        let transparency = material.transparency as f64;
+        let specular = (1.0 - transparency) * material.reflectivity as f64;
+        ray = if lobe < transparency {
+            specular_bounce = true;
+            match refract_or_reflect(&ray, t, &normal, material, sampler) {
+                (ray, true) => {
+                    throughput.component_mul_assign(&albedo);
+                    ray
+                }
+                (ray, false) => ray,
+            }
+        } else if lobe < transparency + specular {
+            specular_bounce = true;
+            let tint = Vector::repeat(1.0).lerp(&albedo, material.metalness as f64);
+            throughput.component_mul_assign(&tint);
+            let mut reflected = ray.bounce(t, facing);
+            if material.roughness > 0.0 {
+                let nudge = Vector::new(
+                    sampler.next_1d() - 0.5,
+                    sampler.next_1d() - 0.5,
+                    sampler.next_1d() - 0.5,
+                );
+                reflected.direction =
+                    (reflected.direction + nudge * material.roughness as f64).normalize();
+            }
+            reflected
+        } else {
+            let (light, visibility) = direct_light(scene, at, &facing, sampler);
+            radiance += throughput.component_mul(&albedo).component_mul(&light);
+            if let (0, Some(first_hit)) = (bounce, &mut first_hit) {
+                first_hit.visibility = Some(visibility.fraction());
+            }
+            specular_bounce = false;
+            let direction = cosine_hemisphere(&facing, sampler.next_2d());
+            bsdf_pdf = direction.dot(&facing).max(0.0) / PI;
+            // Lambertian BRDF times cosine over the pdf leaves just the albedo
+            throughput.component_mul_assign(&albedo);
+            Ray {
+                origin: at,
+                direction,
+            }
+        };
+
+        if bounce >= ROULETTE_START {
+            let survival = throughput.max().clamp(0.05, 0.95);
+            if sampler.next_1d() > survival {
+                break;
+            }
+            throughput /= survival;
+        }
+    }
+    let color = Color::from([radiance.x as f32, radiance.y as f32, radiance.z as f32]);
+    (color, first_hit)
+}
+
+/// Light reaching a diffuse point at `at`, already divided by the BRDF's albedo,
+/// along with how much of the light from [`Scene::lights`] got blocked.
+///
+/// Lights from [`Scene::lights`] follow the same convention as the fast shader,
+/// emissive objects are sampled by area and weighted against BSDF sampling.
+fn direct_light(
+    scene: &Scene,
+    at: Vector,
+    normal: &Vector,
+    sampler: &mut dyn Sampler,
+) -> (Vector, Visibility) {
+    let mut total = Vector::zeros();
+    let mut visibility = Visibility::default();
+    for light in &scene.lights {
+        let incident = match light.incident(at) {
+            Some(incident) => incident,
+            None => continue,
+        };
+        let cos = incident.direction.dot(normal);
+        if cos <= 0.0 {
+            continue;
+        }
+        let shadow_ray = Ray {
+            origin: at,
+            direction: incident.direction,
+        };
+        let blocked = scene
+            .closest_before(&shadow_ray, incident.distance)
+            .is_some();
+        visibility.add(&incident, cos, blocked);
+        if !blocked {
+            total += to_vector(incident.radiance) * cos;
+        }
+    }
+
+    let emitters = scene.emitters();
+    if emitters.is_empty() {
+        return (total, visibility);
+    }
+    let emitter = &scene.shapes()[emitters[sampler.next_index(emitters.len())]];
+    let (area, (point, light_normal)) =
+        match (emitter.area(), emitter.sample_surface(sampler.next_2d())) {
+            (Some(area), Some(sample)) => (area, sample),
+            _ => return (total, visibility),
+        };
+    let to_light = point - at;
+    let distance = to_light.norm();
+    let direction = to_light / distance;
+    let cos = direction.dot(normal);
+    let cos_light = direction.dot(&light_normal).abs();
+    if cos <= 0.0 || float!(cos_light -> 0) {
+        return (total, visibility);
+    }
+    let shadow_ray = Ray {
+        origin: at,
+        direction,
+    };
+    if scene
+        .closest_before(&shadow_ray, distance * (1.0 - 1e-6) - crate::MIN_T)
+        .is_some()
+    {
+        return (total, visibility);
+    }
+    let light_pdf = distance.powi(2) / (cos_light * area * emitters.len() as f64);
+    let bsdf_pdf = cos / PI;
+    let emission = to_vector(scene.materials.get(emitter.material()).emission);
+    // Lambertian BRDF is albedo / π, the albedo is applied by the caller
+    total += emission * (cos / PI / light_pdf * power_heuristic(light_pdf, bsdf_pdf));
+    (total, visibility)
+}
+
+/// Density, in solid angle from the ray's origin, of picking the point where `ray`
+/// hit the object at `index` by sampling emitters. `None` if the object isn't sampled as a light.
+fn emitter_pdf(scene: &Scene, index: usize, ray: &Ray, t: f64, normal: &Vector) -> Option<f64> {
+    let emitters = scene.emitters();
+    emitters.binary_search(&index).ok()?;
+    let area = scene.shapes()[index].area()?;
+    let cos_light = ray.direction.dot(normal).abs();
+    if float!(cos_light -> 0) {
+        return None;
+    }
+    let distance = t * ray.direction.norm();
+    Some(distance.powi(2) / (cos_light * area * emitters.len() as f64))
+}
+
+/// Follows a ray into or off a transparent surface, choosing between reflection
+/// and refraction according to Fresnel. Returns whether the ray went through.
+fn refract_or_reflect(
+    ray: &Ray,
+    t: f64,
+    normal: &Vector,
+    material: &Material,
+    sampler: &mut dyn Sampler,
+) -> (Ray, bool) {
+    let ior = material.ior as f64;
+    let (normal, eta) = if ray.direction.dot(normal) > 0.0 {
+        (-normal, ior)
+    } else {
+        (*normal, 1.0 / ior)
+    };
+    match ray.refract(t, normal, eta) {
+        Some(refracted) => {
+            let cos = if eta < 1.0 {
+                -ray.direction.dot(&normal)
+            } else {
+                refracted.direction.dot(&-normal)
+            };
+            if sampler.next_1d() < schlick(cos, eta) {
+                (ray.bounce(t, normal), false)
+            } else {
+                (refracted, true)
+            }
+        }
+        // Total internal reflection
+        None => (ray.bounce(t, normal), false),
+    }
+}
+
+/// Light coming from rays that escape the scene
+fn environment(scene: &Scene) -> Vector {
+    scene
+        .lights
+        .iter()
+        .filter_map(|light| light.ambient_radiance())
+        .fold(to_vector(scene.background), |total, ambient| {
+            total + to_vector(ambient)
+        })
+}
+
+/// Weight of a sample taken with density `pdf` when another strategy could have
+/// produced it with density `other_pdf`
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0.0 {
+        0.0
+    } else {
+        a / (a + b)
+    }
+}
+
+fn to_vector(color: Color) -> Vector {
+    let [r, g, b] = color.0;
+    Vector::new(r as f64, g as f64, b as f64)
+}
@@ -0,0 +1,172 @@
+use image::Pixel;
+
+use crate::{
+    color::{Color, BLACK},
+    integrator::{schlick, FirstHit, Integrator, Visibility},
+    material::Material,
+    sampling::Sampler,
+    scene::{Ray, Scene},
+    shapes::{Object, Vector},
+};
+
+/// Classic recursive ray tracer: direct light with hard shadows,
+/// plus reflections and refractions followed until `max_depth` bounces
+#[derive(Clone, Copy, Debug)]
+pub struct Whitted {
+    pub max_depth: u32,
+}
+impl Integrator for Whitted {
+    fn li(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        trace(ray, scene, self.max_depth, sampler).0
+    }
+    fn li_with_first_hit(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+    ) -> (Color, Option<FirstHit>) {
+        trace(ray, scene, self.max_depth, sampler)
+    }
+}
+
+/// Finds the color seen along `ray`, following reflections and refractions
+/// until `depth` bounces have been spent, and what the ray hit first
+fn trace(
+    ray: &Ray,
+    scene: &Scene,
+    depth: u32,
+    sampler: &mut dyn Sampler,
+) -> (Color, Option<FirstHit>) {
+    let (hit, index) = match scene.closest_index(ray) {
+        Some(closest) => closest,
+        None => return (scene.background, None),
+    };
+    let obj = &scene.shapes()[index];
+    let material = scene.materials.get(obj.material());
+    let t = hit.t;
+    let at = ray.at(t);
+    let normal = obj.hit_normal(ray, &hit);
+    // Surfaces like triangles are seen from both sides, shade the one facing the ray
+    let facing = if ray.direction.dot(&normal) > 0.0 {
+        -normal
+    } else {
+        normal
+    };
+    let (light, visibility) = direct_light(at, &facing, scene);
+    let mut color = material.albedo.map2(&light, |albedo, light| albedo * light);
+
+    let reflectivity = material.reflectivity;
+    if reflectivity > 0.0 && depth > 0 {
+        let mut reflected = ray.bounce(t, facing);
+        if material.roughness > 0.0 {
+            // Blur the reflection by nudging it in a random direction
+            let nudge = Vector::new(
+                sampler.next_1d() - 0.5,
+                sampler.next_1d() - 0.5,
+                sampler.next_1d() - 0.5,
+            );
+            reflected.direction =
+                (reflected.direction + nudge * material.roughness as f64).normalize();
+        }
+        let (mut mirror, _) = trace(&reflected, scene, depth - 1, sampler);
+        // Metals tint their reflections with their own color
+        mirror.apply2(&material.albedo, |m, albedo| {
+            m * (1.0 - material.metalness + albedo * material.metalness)
+        });
+        color.apply2(&mirror, |diffuse, mirror| {
+            diffuse * (1.0 - reflectivity) + mirror * reflectivity
+        });
+    }
+    let transparency = material.transparency;
+    if transparency > 0.0 && depth > 0 {
+        let glass = trace_dielectric(ray, t, normal, material, scene, depth - 1, sampler);
+        color.apply2(&glass, |opaque, glass| {
+            opaque * (1.0 - transparency) + glass * transparency
+        });
+    }
+    color.apply2(&material.emission, |c, emission| c + emission);
+    let first_hit = FirstHit {
+        hit,
+        index,
+        normal,
+        visibility: Some(visibility.fraction()),
+    };
+    (color, Some(first_hit))
+}
+
+/// Splits a ray hitting a transparent surface into its reflected and refracted parts,
+/// weighted by how much light each one carries according to Fresnel
+fn trace_dielectric(
+    ray: &Ray,
+    t: f64,
+    normal: Vector,
+    material: &Material,
+    scene: &Scene,
+    depth: u32,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    let ior = material.ior as f64;
+    // Normals point outwards, so if the ray goes along it we are leaving the object
+    let (normal, eta) = if ray.direction.dot(&normal) > 0.0 {
+        (-normal, ior)
+    } else {
+        (normal, 1.0 / ior)
+    };
+    let (reflected, _) = trace(&ray.bounce(t, normal), scene, depth, sampler);
+    let refracted = match ray.refract(t, normal, eta) {
+        Some(refracted) => refracted,
+        // Total internal reflection
+        None => return reflected,
+    };
+    // Schlick's approximation must use the angle on the less dense side
+    let cos = if eta < 1.0 {
+        -ray.direction.dot(&normal)
+    } else {
+        refracted.direction.dot(&-normal)
+    };
+    let kr = schlick(cos, eta) as f32;
+    let (mut transmitted, _) = trace(&refracted, scene, depth, sampler);
+    // Colored glass filters the light going through it
+    transmitted.apply2(&material.albedo, |c, albedo| c * albedo);
+    transmitted.map2(&reflected, |transmitted, reflected| {
+        reflected * kr + transmitted * (1.0 - kr)
+    })
+}
+
+/// Sums the light reaching `at` from every light source that isn't blocked by an object,
+/// along with how much of it got blocked
+fn direct_light(at: Vector, normal: &Vector, scene: &Scene) -> (Color, Visibility) {
+    let mut total = BLACK;
+    let mut visibility = Visibility::default();
+    for light in &scene.lights {
+        if let Some(ambient) = light.ambient_radiance() {
+            total.apply2(&ambient, |c1, c2| c1 + c2);
+            continue;
+        }
+        let incident = match light.incident(at) {
+            Some(incident) => incident,
+            None => continue,
+        };
+        let cos = incident.direction.dot(normal);
+        if cos <= 0.0 {
+            continue;
+        }
+        let shadow_ray = Ray {
+            origin: at,
+            direction: incident.direction,
+        };
+        let blocked = scene
+            .closest_before(&shadow_ray, incident.distance)
+            .is_some();
+        visibility.add(&incident, cos, blocked);
+        if !blocked {
+            total.apply2(&incident.radiance, |c1, c2| c1 + c2 * cos as f32);
+        }
+    }
+    debug_assert!(
+        total.0.iter().all(|c| c.is_sign_positive()),
+        "light = {:?}",
+        total.0
+    );
+    (total, visibility)
+}
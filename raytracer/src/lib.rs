@@ -14,6 +14,12 @@ macro_rules! float {
     };
 }
 
+pub mod accel;
 pub mod color;
+pub mod denoise;
+pub mod integrator;
+pub mod material;
+pub mod model;
+pub mod sampling;
 pub mod scene;
 pub mod shapes;
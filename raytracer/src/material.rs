@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::color::{Color, BLACK, MAGENTA, WHITE};
+
+/// Name used by shapes that were not given a material
+pub const DEFAULT_MATERIAL: &str = "default";
+
+/// Describes how a surface interacts with light
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct Material {
+    /// Base color of the surface
+    #[cfg_attr(feature = "serde", serde(with = "crate::color::RgbDef"))]
+    pub albedo: Color,
+    /// Light given off by the surface itself
+    #[cfg_attr(feature = "serde", serde(with = "crate::color::RgbDef"))]
+    pub emission: Color,
+    /// How blurry reflections are, from `0.0` (sharp) to `1.0` (very blurry)
+    pub roughness: f32,
+    /// How much reflections are tinted by the albedo, from `0.0` (dielectric) to `1.0` (metal)
+    pub metalness: f32,
+    /// Fraction of light mirrored off the surface, from `0.0` (matte) to `1.0` (perfect mirror)
+    pub reflectivity: f32,
+    /// Fraction of light that goes through the surface, from `0.0` (opaque) to `1.0` (clear)
+    pub transparency: f32,
+    /// Index of refraction
+    pub ior: f32,
+}
+impl Material {
+    /// Used when a shape references a material that doesn't exist
+    pub const MISSING: Material = Material {
+        albedo: MAGENTA,
+        ..Material::DIFFUSE
+    };
+    const DIFFUSE: Material = Material {
+        albedo: WHITE,
+        emission: BLACK,
+        roughness: 0.0,
+        metalness: 0.0,
+        reflectivity: 0.0,
+        transparency: 0.0,
+        ior: 1.5,
+    };
+
+    /// Creates a matte material of the given color
+    pub fn diffuse(albedo: Color) -> Self {
+        Material {
+            albedo,
+            ..Material::DIFFUSE
+        }
+    }
+}
+impl Default for Material {
+    fn default() -> Self {
+        Material::DIFFUSE
+    }
+}
+
+/// Named materials shared by the objects of a scene
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct Materials(BTreeMap<String, Material>);
+impl Materials {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn insert(&mut self, name: impl Into<String>, material: Material) {
+        self.0.insert(name.into(), material);
+    }
+    /// Looks up a material by name, falling back to [`Material::MISSING`]
+    pub fn get(&self, name: &str) -> &Material {
+        self.0.get(name).unwrap_or(&Material::MISSING)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Material)> {
+        self.0.iter()
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use image::Rgb;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    color::BLACK,
+    material::{Material, Materials, DEFAULT_MATERIAL},
+    shapes::{Mesh, MeshError, Shape, Transform, Vector},
+};
+
+/// A Wavefront OBJ file placed in the scene
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Model {
+    /// Path to the `.obj` file, relative to the scene file
+    pub path: PathBuf,
+    /// Moves the model from the coordinates it was modelled in into the scene
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub transform: Transform,
+    /// Material used instead of the ones in the model's MTL files
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub material: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ModelError {
+    /// The OBJ or MTL file couldn't be read or parsed
+    Load(PathBuf, tobj::LoadError),
+    /// The file was parsed but describes invalid geometry
+    Mesh(PathBuf, MeshError),
+}
+impl Display for ModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelError::Load(path, e) => write!(f, "Failed to load {}: {e}", path.display()),
+            ModelError::Mesh(path, e) => write!(f, "Invalid mesh in {}: {e}", path.display()),
+        }
+    }
+}
+impl std::error::Error for ModelError {}
+
+impl Model {
+    /// Reads the model's geometry, turning every object in the file into a [`Mesh`].
+    ///
+    /// Materials from MTL files are added to `materials`, prefixed with the model's path
+    /// (`chair/model.obj/wood`) so that different models don't clash.
+    pub fn load(
+        &self,
+        base_dir: &Path,
+        materials: &mut Materials,
+    ) -> Result<Vec<Shape>, ModelError> {
+        let path = base_dir.join(&self.path);
+        let (models, mtl) = tobj::load_obj(&path, &tobj::GPU_LOAD_OPTIONS)
+            .map_err(|e| ModelError::Load(path.clone(), e))?;
+        // A missing MTL file isn't fatal, the meshes will use the fallback material
+        let mtl = mtl.unwrap_or_default();
+
+        // The whole path, as files with the same name can sit in different folders
+        let prefix = self.path.to_string_lossy().replace('\\', "/");
+        let names: Vec<String> = mtl
+            .iter()
+            .map(|m| format!("{prefix}/{name}", name = m.name))
+            .collect();
+        if self.material.is_none() {
+            for (name, m) in names.iter().zip(&mtl) {
+                materials.insert(name.clone(), convert_material(m));
+            }
+        }
+
+        // The transform is baked into the vertices so the meshes' hierarchies fit them tightly
+        models
+            .into_iter()
+            .map(|model| {
+                let mesh = model.mesh;
+                let vertices = mesh
+                    .positions
+                    .chunks_exact(3)
+                    .map(|p| {
+                        let p = Vector::new(p[0] as _, p[1] as _, p[2] as _);
+                        self.transform.point(&p)
+                    })
+                    .collect();
+                let normals = mesh
+                    .normals
+                    .chunks_exact(3)
+                    .map(|n| {
+                        self.transform
+                            .normal(&Vector::new(n[0] as _, n[1] as _, n[2] as _))
+                    })
+                    .collect();
+                let indices = mesh
+                    .indices
+                    .chunks_exact(3)
+                    .map(|i| [i[0], i[1], i[2]])
+                    .collect();
+                let mut shape = Mesh::new(vertices, normals, indices)
+                    .map_err(|e| ModelError::Mesh(path.clone(), e))?;
+                shape.material = match (&self.material, mesh.material_id) {
+                    (Some(material), _) => material.clone(),
+                    (None, Some(id)) if id < names.len() => names[id].clone(),
+                    (None, _) => DEFAULT_MATERIAL.to_string(),
+                };
+                Ok(Shape::Mesh(shape))
+            })
+            .collect()
+    }
+}
+
+/// Approximates an MTL material with ours
+fn convert_material(m: &tobj::Material) -> Material {
+    let rgb = |c: [f32; 3]| Rgb(c);
+    let albedo = m.diffuse.map(rgb).unwrap_or(Material::default().albedo);
+    let emission = m
+        .unknown_param
+        .get("Ke")
+        .and_then(|ke| {
+            let c: Vec<f32> = ke
+                .split_whitespace()
+                .filter_map(|c| c.parse().ok())
+                .collect();
+            (c.len() == 3).then(|| Rgb([c[0], c[1], c[2]]))
+        })
+        .unwrap_or(BLACK);
+    // Illumination models 3 and up have ray traced reflections
+    let reflectivity = match (m.illumination_model, m.specular) {
+        (Some(illum), Some([r, g, b])) if illum >= 3 => (r + g + b) / 3.0,
+        _ => 0.0,
+    };
+    Material {
+        albedo,
+        emission,
+        // Phong exponents go up to 1000 for very sharp highlights
+        roughness: m
+            .shininess
+            .map(|ns| (2.0 / (ns + 2.0)).sqrt())
+            .unwrap_or(1.0),
+        metalness: 0.0,
+        reflectivity,
+        transparency: 1.0 - m.dissolve.unwrap_or(1.0),
+        ior: m.optical_density.unwrap_or(1.5),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::shapes::Object;
+
+    /// Writes a one triangle model using a `wood` material of the given color
+    fn write_model(dir: &Path, folder: &str, diffuse: &str) -> PathBuf {
+        let folder = dir.join(folder);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(
+            folder.join("model.obj"),
+            "mtllib model.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl wood\nf 1 2 3\n",
+        )
+        .unwrap();
+        fs::write(
+            folder.join("model.mtl"),
+            format!("newmtl wood\nKd {diffuse}\n"),
+        )
+        .unwrap();
+        folder.join("model.obj")
+    }
+
+    #[test]
+    fn same_named_models_keep_their_own_materials() {
+        let dir = std::env::temp_dir().join(format!("raytracer-models-{}", std::process::id()));
+        write_model(&dir, "chair", "1 0 0");
+        write_model(&dir, "table", "0 0 1");
+        let mut materials = Materials::new();
+        let mut loaded = Vec::new();
+        for path in ["chair/model.obj", "table/model.obj"] {
+            let model = Model {
+                path: path.into(),
+                transform: Transform::IDENTITY,
+                material: None,
+            };
+            loaded.extend(model.load(&dir, &mut materials).unwrap());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded[0].material(), "chair/model.obj/wood");
+        assert_eq!(loaded[1].material(), "table/model.obj/wood");
+        assert_eq!(
+            materials.get("chair/model.obj/wood").albedo,
+            Rgb([1.0, 0.0, 0.0])
+        );
+        assert_eq!(
+            materials.get("table/model.obj/wood").albedo,
+            Rgb([0.0, 0.0, 1.0])
+        );
+    }
+}
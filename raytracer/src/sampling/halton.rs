@@ -0,0 +1,56 @@
+use crate::sampling::{hash, to_unit, Sampler};
+
+/// Bases of the first dimensions, later dimensions get plain random numbers
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Halton sequence, using the radical inverse in a different prime base for every dimension.
+///
+/// Every pixel shifts the sequence by a random offset (Cranley-Patterson rotation)
+/// so that neighbouring pixels don't share the same pattern.
+#[derive(Clone, Debug, Default)]
+pub struct Halton {
+    pixel_seed: u64,
+    index: u32,
+    dimension: usize,
+}
+impl Halton {
+    pub fn new() -> Self {
+        Halton::default()
+    }
+}
+impl Sampler for Halton {
+    fn start_sample(&mut self, (x, y): (u32, u32), index: u32) {
+        self.pixel_seed = hash(&[x as u64, y as u64]);
+        self.index = index;
+        self.dimension = 0;
+    }
+    fn next_1d(&mut self) -> f64 {
+        let offset = to_unit(hash(&[self.pixel_seed, self.dimension as u64]));
+        let value = match PRIMES.get(self.dimension) {
+            Some(&base) => (radical_inverse(base, self.index) + offset).fract(),
+            None => to_unit(hash(&[
+                self.pixel_seed,
+                self.dimension as u64,
+                self.index as u64,
+            ])),
+        };
+        self.dimension += 1;
+        value
+    }
+}
+
+/// Mirrors the digits of `index` written in `base` around the decimal point
+fn radical_inverse(base: u32, mut index: u32) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut scale = inv_base;
+    let mut value = 0.0;
+    while index > 0 {
+        value += (index % base) as f64 * scale;
+        index /= base;
+        scale *= inv_base;
+    }
+    value
+}
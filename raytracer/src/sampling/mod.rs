@@ -0,0 +1,161 @@
+use std::{
+    f64::consts::{FRAC_PI_4, PI},
+    fmt::Display,
+    str::FromStr,
+};
+
+use fastrand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::shapes::{Vector, XP, YP};
+
+mod halton;
+mod sobol;
+mod stratified;
+
+pub use halton::Halton;
+pub use sobol::Sobol;
+pub use stratified::Stratified;
+
+/// Source of the numbers used to take random decisions while rendering.
+///
+/// Every call asks for a new dimension of the current sample. Samplers other than plain
+/// random numbers spread the values of each dimension evenly across the samples of a pixel.
+pub trait Sampler {
+    /// Moves to sample number `index` of `pixel`, starting again from the first dimension
+    fn start_sample(&mut self, _pixel: (u32, u32), _index: u32) {}
+    /// Number in `[0, 1)`
+    fn next_1d(&mut self) -> f64;
+    /// Point in the unit square
+    fn next_2d(&mut self) -> (f64, f64) {
+        (self.next_1d(), self.next_1d())
+    }
+    /// Index in `0..len`, `len` must not be zero
+    fn next_index(&mut self, len: usize) -> usize {
+        ((self.next_1d() * len as f64) as usize).min(len - 1)
+    }
+}
+impl Sampler for Rng {
+    fn next_1d(&mut self) -> f64 {
+        self.f64()
+    }
+}
+
+/// Samplers that can be picked from a scene file or the command line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SamplerKind {
+    /// Independent random numbers
+    Random,
+    /// Jittered grid, one cell per sample
+    #[default]
+    Stratified,
+    /// Halton low-discrepancy sequence
+    Halton,
+    /// Owen-scrambled Sobol low-discrepancy sequence
+    Sobol,
+}
+impl SamplerKind {
+    const ALL: [SamplerKind; 4] = [
+        SamplerKind::Random,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ];
+
+    /// Creates a sampler for pixels taking `samples` samples each
+    pub fn build(self, samples: u32) -> Box<dyn Sampler + Send> {
+        match self {
+            SamplerKind::Random => Box::new(Rng::new()),
+            SamplerKind::Stratified => Box::new(Stratified::new(samples)),
+            SamplerKind::Halton => Box::new(Halton::new()),
+            SamplerKind::Sobol => Box::new(Sobol::new()),
+        }
+    }
+}
+impl FromStr for SamplerKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SamplerKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_string() == s)
+            .ok_or_else(|| {
+                let names: Vec<String> = SamplerKind::ALL.iter().map(|k| k.to_string()).collect();
+                format!(
+                    "unknown sampler `{s}`, expected one of: {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+impl Display for SamplerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SamplerKind::Random => "random",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::Halton => "halton",
+            SamplerKind::Sobol => "sobol",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Scrambles a list of numbers into a well mixed 64 bit value
+fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x853c_49e6_748f_ea9b, |h, &v| {
+        // SplitMix64 finalizer
+        let mut x = (h ^ v).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        x ^ (x >> 31)
+    })
+}
+
+/// Maps a hash to a number in `[0, 1)`
+fn to_unit(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Maps a point in the unit square to the unit disk, keeping evenly spread points evenly spread
+pub fn concentric_disk((u, v): (f64, f64)) -> (f64, f64) {
+    let (u, v) = (u * 2.0 - 1.0, v * 2.0 - 1.0);
+    if u == 0.0 && v == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if u.abs() > v.abs() {
+        (u, FRAC_PI_4 * (v / u))
+    } else {
+        (v, 2.0 * FRAC_PI_4 - FRAC_PI_4 * (u / v))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+/// Direction around `normal` picked with a probability proportional to the cosine
+/// of its angle to the normal. The density is `cos / π`.
+pub fn cosine_hemisphere(normal: &Vector, u: (f64, f64)) -> Vector {
+    let (x, y) = concentric_disk(u);
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    (tangent * x + bitangent * y + normal * z).normalize()
+}
+
+/// Point on the unit sphere, every point being equally likely
+pub fn uniform_sphere((u, v): (f64, f64)) -> Vector {
+    let z = 1.0 - 2.0 * u;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+    Vector::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Two unit vectors perpendicular to `normal` and to each other
+pub fn orthonormal_basis(normal: &Vector) -> (Vector, Vector) {
+    let helper = if normal.x.abs() < 0.9 { XP } else { YP };
+    let tangent = helper.cross(normal).normalize();
+    let bitangent = normal.cross(&tangent);
+    (tangent, bitangent)
+}
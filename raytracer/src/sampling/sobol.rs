@@ -0,0 +1,78 @@
+use crate::sampling::{hash, Sampler};
+
+/// First two dimensions of the Sobol sequence with hash-based Owen scrambling,
+/// following Burley's "Practical Hash-based Owen Scrambling".
+///
+/// Every request gets its own independently shuffled and scrambled copy of the sequence,
+/// so that any number of dimensions can be used without them being correlated.
+#[derive(Clone, Debug, Default)]
+pub struct Sobol {
+    pixel_seed: u64,
+    index: u32,
+    dimension: u64,
+}
+impl Sobol {
+    pub fn new() -> Self {
+        Sobol::default()
+    }
+    /// Seeds for shuffling the current dimension and scrambling each of its axes
+    fn seeds(&self) -> [u32; 3] {
+        let h = |axis: u64| hash(&[self.pixel_seed, self.dimension, axis]) as u32;
+        [h(0), h(1), h(2)]
+    }
+}
+impl Sampler for Sobol {
+    fn start_sample(&mut self, (x, y): (u32, u32), index: u32) {
+        self.pixel_seed = hash(&[x as u64, y as u64]);
+        self.index = index;
+        self.dimension = 0;
+    }
+    fn next_1d(&mut self) -> f64 {
+        let [shuffle, scramble, _] = self.seeds();
+        let index = nested_uniform_scramble(self.index, shuffle);
+        let x = nested_uniform_scramble(index.reverse_bits(), scramble);
+        self.dimension += 1;
+        to_f64(x)
+    }
+    fn next_2d(&mut self) -> (f64, f64) {
+        let [shuffle, scramble_x, scramble_y] = self.seeds();
+        let index = nested_uniform_scramble(self.index, shuffle);
+        let x = nested_uniform_scramble(index.reverse_bits(), scramble_x);
+        let y = nested_uniform_scramble(sobol_second(index), scramble_y);
+        self.dimension += 1;
+        (to_f64(x), to_f64(y))
+    }
+}
+
+/// Second dimension of the Sobol sequence, the first one is just the reversed bits
+fn sobol_second(mut index: u32) -> u32 {
+    let mut direction = 1 << 31;
+    let mut value = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            value ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    value
+}
+
+/// Randomly flips bits of `x` based on the bits above them, keeping a sequence's strata
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Hash where every bit only depends on the bits below it
+fn laine_karras(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+fn to_f64(x: u32) -> f64 {
+    x as f64 / (1u64 << 32) as f64
+}
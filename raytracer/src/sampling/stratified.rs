@@ -0,0 +1,98 @@
+use crate::sampling::{hash, to_unit, Sampler};
+
+/// Splits every dimension into as many cells as samples per pixel and puts one
+/// sample at a random position in each cell, visiting the cells in a random order.
+///
+/// Points in the square use a grid as close to square as possible.
+/// Samples past `samples` start over with a new order.
+#[derive(Clone, Debug)]
+pub struct Stratified {
+    samples: u32,
+    pixel_seed: u64,
+    index: u32,
+    dimension: u64,
+}
+impl Stratified {
+    pub fn new(samples: u32) -> Self {
+        Stratified {
+            samples: samples.max(1),
+            pixel_seed: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+    /// Cell of `cells` where the current sample goes in the current dimension
+    fn cell(&self, cells: u32) -> u32 {
+        let pass = self.index / self.samples;
+        let seed = hash(&[self.pixel_seed, self.dimension, pass as u64]) as u32;
+        permute(self.index % self.samples, cells, seed)
+    }
+    /// Random offset inside a cell
+    fn jitter(&self, axis: u64) -> f64 {
+        to_unit(hash(&[
+            self.pixel_seed,
+            self.dimension,
+            axis,
+            self.index as u64,
+        ]))
+    }
+}
+impl Sampler for Stratified {
+    fn start_sample(&mut self, (x, y): (u32, u32), index: u32) {
+        self.pixel_seed = hash(&[x as u64, y as u64]);
+        self.index = index;
+        self.dimension = 0;
+    }
+    fn next_1d(&mut self) -> f64 {
+        let cell = self.cell(self.samples);
+        let value = (cell as f64 + self.jitter(0)) / self.samples as f64;
+        self.dimension += 1;
+        value
+    }
+    fn next_2d(&mut self) -> (f64, f64) {
+        let columns = (self.samples as f64).sqrt() as u32;
+        let rows = self.samples.div_ceil(columns);
+        // With a non square amount of samples some cells stay empty
+        let cell = self.cell(columns * rows);
+        let x = ((cell % columns) as f64 + self.jitter(0)) / columns as f64;
+        let y = ((cell / columns) as f64 + self.jitter(1)) / rows as f64;
+        self.dimension += 1;
+        (x, y)
+    }
+}
+
+/// Shuffles `0..len`, giving where `index` ends up in the permutation picked by `seed`.
+///
+/// From Kensler's "Correlated Multi-Jittered Sampling", it doesn't need to store the permutation.
+fn permute(mut index: u32, len: u32, seed: u32) -> u32 {
+    let mut mask = len - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+    // Shuffle inside the next power of two and try again until the result falls in range
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170_893d);
+        index ^= seed >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929_eb3f);
+        index ^= seed >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935_fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dc_b303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e50_1cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860_a3df);
+        index &= mask;
+        index ^= index >> 5;
+        if index < len {
+            return (index.wrapping_add(seed)) % len;
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use image::{Pixel, Rgb32FImage};
+
+use crate::{
+    integrator::Integrator,
+    scene::{parallel_render_samples, Scene},
+};
+
+/// Running average of successive renders of the same view, so that noise fades away
+/// the longer nothing changes. Call [`Accumulator::reset`] when the scene or camera moves.
+#[derive(Clone)]
+pub struct Accumulator {
+    /// Sum of the linear light of every pass
+    sum: Rgb32FImage,
+    /// Scratch buffer the next pass is rendered into
+    pass: Rgb32FImage,
+    passes: u32,
+}
+impl Accumulator {
+    pub fn new(width: u32, height: u32) -> Self {
+        Accumulator {
+            sum: Rgb32FImage::new(width, height),
+            pass: Rgb32FImage::new(width, height),
+            passes: 0,
+        }
+    }
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.sum.dimensions()
+    }
+    /// Passes averaged so far
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+    /// Throws away every pass, the next one starts a new image
+    pub fn reset(&mut self) {
+        self.sum.pixels_mut().for_each(|pixel| pixel.0 = [0.0; 3]);
+        self.passes = 0;
+    }
+    /// Renders the scene again with samples not used by the previous passes, using every core,
+    /// and adds it to the average
+    pub fn add_pass(&mut self, scene: &Scene, integrator: &dyn Integrator) {
+        let first_sample = self.passes.saturating_mul(scene.settings.samples.max(1));
+        parallel_render_samples(&mut self.pass, scene, integrator, first_sample);
+        for (sum, pass) in self.sum.pixels_mut().zip(self.pass.pixels()) {
+            sum.apply2(pass, |sum, pass| sum + pass);
+        }
+        self.passes += 1;
+    }
+    /// Average of the passes so far, in linear light
+    pub fn image(&self) -> Rgb32FImage {
+        let scale = 1.0 / self.passes.max(1) as f32;
+        let mut image = self.sum.clone();
+        image
+            .pixels_mut()
+            .for_each(|pixel| pixel.apply(|c| c * scale));
+        image
+    }
+}
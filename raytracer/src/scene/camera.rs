@@ -1,41 +1,423 @@
-use crate::{scene::Ray, shapes::Vector};
+use std::fmt::Display;
 
-/// Perspective camera
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    sampling::concentric_disk,
+    scene::Ray,
+    shapes::{Vector, XP, YP, ZP},
+};
+
+/// How the camera maps points in the image to directions in the scene
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(tag = "type"))]
+pub enum Projection {
+    /// Pinhole or thin lens camera using the camera's field of view
+    #[default]
+    Perspective,
+    /// Parallel rays, objects keep their size no matter how far they are
+    Orthographic {
+        /// Size of the image's height in scene units
+        height: f64,
+    },
+    /// Equidistant fisheye lens
+    Fisheye {
+        /// Angle covered by the image's diagonal, in degrees. May go above 180
+        fov: f64,
+    },
+    /// Full 360° by 180° panorama, longitude along the width and latitude along the height
+    Equirectangular,
+}
+
+/// Camera looking from `origin` towards `target`
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "CameraDef", into = "CameraDef")
+)]
 pub struct Camera {
     origin: Vector,
+    target: Vector,
+    /// Up direction requested by the user, not necessarily perpendicular to `forward`
+    up_guide: Vector,
+    /// Vertical field of view in degrees
+    fov: f64,
+    /// Width over height of the image
+    aspect: f64,
+    projection: Projection,
+    /// Radius of the lens, zero for a pinhole camera with everything in focus
+    aperture: f64,
+    /// Distance from the camera to the plane that is in focus, defaults to the target's
+    focus_distance: Option<f64>,
     forward: Vector,
     up: Vector,
     right: Vector,
     width: f64,
     height: f64,
 }
+
+/// Settings that can't make a working camera
+#[derive(Debug)]
+pub enum CameraError {
+    /// The vertical field of view isn't strictly between 0 and 180 degrees
+    Fov(f64),
+    /// The width over height ratio isn't a positive number
+    Aspect(f64),
+    /// The lens radius is negative
+    Aperture(f64),
+}
+impl Display for CameraError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CameraError::Fov(fov) => {
+                write!(f, "Camera fov must be between 0 and 180 degrees, got {fov}")
+            }
+            CameraError::Aspect(aspect) => {
+                write!(f, "Camera aspect must be positive, got {aspect}")
+            }
+            CameraError::Aperture(aperture) => {
+                write!(f, "Camera aperture can't be negative, got {aperture}")
+            }
+        }
+    }
+}
+impl std::error::Error for CameraError {}
+
+/// Builds a [`Camera`], any setting left out takes the value of [`Camera::default`]
+#[derive(Clone, Default)]
+pub struct CameraBuilder {
+    origin: Option<Vector>,
+    target: Option<Vector>,
+    up: Option<Vector>,
+    fov: Option<f64>,
+    aspect: Option<f64>,
+    projection: Option<Projection>,
+    aperture: Option<f64>,
+    focus_distance: Option<f64>,
+}
+impl CameraBuilder {
+    pub fn origin(mut self, origin: Vector) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+    /// Point the camera looks at
+    pub fn target(mut self, target: Vector) -> Self {
+        self.target = Some(target);
+        self
+    }
+    /// Direction that will point to the top of the image
+    pub fn up(mut self, up: Vector) -> Self {
+        self.up = Some(up);
+        self
+    }
+    /// Vertical field of view in degrees
+    pub fn fov(mut self, fov: f64) -> Self {
+        self.fov = Some(fov);
+        self
+    }
+    /// Width over height of the image, [`render`](crate::scene::render) overrides it
+    /// with the framebuffer's
+    pub fn aspect(mut self, aspect: f64) -> Self {
+        self.aspect = Some(aspect);
+        self
+    }
+    pub fn projection(mut self, projection: Projection) -> Self {
+        self.projection = Some(projection);
+        self
+    }
+    /// Radius of the lens, bigger apertures blur out of focus objects more
+    pub fn aperture(mut self, aperture: f64) -> Self {
+        self.aperture = Some(aperture);
+        self
+    }
+    /// Distance from the camera to the plane that is in focus, defaults to the target's
+    pub fn focus_distance(mut self, focus_distance: f64) -> Self {
+        self.focus_distance = Some(focus_distance);
+        self
+    }
+    pub fn build(self) -> Result<Camera, CameraError> {
+        let mut camera = Camera {
+            origin: self.origin.unwrap_or(Camera::DEFAULT_ORIGIN),
+            target: self.target.unwrap_or(Camera::DEFAULT_TARGET),
+            up_guide: self.up.unwrap_or(YP),
+            fov: self.fov.unwrap_or(Camera::DEFAULT_FOV),
+            aspect: self.aspect.unwrap_or(Camera::DEFAULT_ASPECT),
+            projection: self.projection.unwrap_or_default(),
+            aperture: self.aperture.unwrap_or(0.0),
+            focus_distance: self.focus_distance,
+            forward: ZP,
+            up: YP,
+            right: XP,
+            width: 0.0,
+            height: 0.0,
+        };
+        camera.validate()?;
+        camera.update();
+        Ok(camera)
+    }
+}
+
 impl Camera {
-    pub fn new(fov: f64, target: Vector, origin: Vector) -> Self {
-        debug_assert!(fov > 0.0 && fov < 90.0);
-        const UPGUIDE: Vector = na::Vector3::new(0.0, -1.0, -1.0);
-        let forward = (target - origin).normalize();
-        let right = forward.cross(&UPGUIDE).normalize();
-        let up = right.cross(&forward).normalize();
-        let height = fov.tan();
-        const ASPECT_RATIO: f64 = 16.0 / 9.0;
-        let width = height * ASPECT_RATIO;
-        Camera {
-            origin,
-            forward,
-            up,
-            right,
-            width,
-            height,
+    const DEFAULT_ORIGIN: Vector = na::Vector3::new(0.0, 0.0, -1.0);
+    const DEFAULT_TARGET: Vector = na::Vector3::new(0.0, 0.0, 0.0);
+    const DEFAULT_FOV: f64 = 35.0;
+    const DEFAULT_ASPECT: f64 = 16.0 / 9.0;
+
+    pub fn builder() -> CameraBuilder {
+        CameraBuilder::default()
+    }
+    /// Camera at `origin` looking at `target` with a vertical field of view of `fov` degrees
+    pub fn new(fov: f64, target: Vector, origin: Vector) -> Result<Self, CameraError> {
+        Camera::builder()
+            .fov(fov)
+            .target(target)
+            .origin(origin)
+            .build()
+    }
+
+    /// Checks the settings a scene file could get wrong
+    fn validate(&self) -> Result<(), CameraError> {
+        if !(self.fov > 0.0 && self.fov < 180.0) {
+            return Err(CameraError::Fov(self.fov));
+        }
+        if self.aspect <= 0.0 || !self.aspect.is_finite() {
+            return Err(CameraError::Aspect(self.aspect));
+        }
+        if self.aperture < 0.0 || !self.aperture.is_finite() {
+            return Err(CameraError::Aperture(self.aperture));
         }
+        Ok(())
     }
+    /// Recomputes the camera's basis and image plane after a setting changed
+    fn update(&mut self) {
+        debug_assert!(
+            self.fov > 0.0 && self.fov < 180.0,
+            "fov must be in (0, 180), got {}",
+            self.fov
+        );
+        debug_assert!(self.aspect > 0.0, "aspect must be positive");
+        debug_assert!(self.aperture >= 0.0, "aperture can't be negative");
+        self.forward = (self.target - self.origin).try_normalize(0.0).unwrap_or(ZP);
+        let guide = self.up_guide.try_normalize(0.0).unwrap_or(YP);
+        let right = match guide.cross(&self.forward).try_normalize(1e-9) {
+            Some(right) => right,
+            // Looking straight along the up vector, any perpendicular direction will do
+            None => {
+                let fallback = if self.forward.x.abs() < 0.9 { XP } else { ZP };
+                fallback.cross(&self.forward).normalize()
+            }
+        };
+        self.right = right;
+        self.up = self.forward.cross(&self.right);
+        self.height = (self.fov.to_radians() / 2.0).tan();
+        self.width = self.height * self.aspect;
+    }
+
+    /// Ray going through the image at `(x, y)`, both from -1 to 1
+    /// with `(-1, -1)` being the top left corner
     pub fn ray(&self, x: f64, y: f64) -> Ray {
         // debug_assert!(x >= -1.0 && x <= 1.0, "({x}; {y})");
         // debug_assert!(y >= -1.0 && y <= 1.0, "({x}; {y})");
-        let direction: na::Vector3<_> =
-            self.forward + self.right * x * self.width + self.up * y * self.height;
-        Ray {
-            origin: self.origin,
-            direction: direction.normalize(),
+        match self.projection {
+            Projection::Perspective => {
+                let direction: na::Vector3<_> =
+                    self.forward + self.right * x * self.width - self.up * y * self.height;
+                Ray {
+                    origin: self.origin,
+                    direction: direction.normalize(),
+                }
+            }
+            Projection::Orthographic { height } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect;
+                Ray {
+                    origin: self.origin + self.right * x * half_width - self.up * y * half_height,
+                    direction: self.forward,
+                }
+            }
+            Projection::Fisheye { fov } => {
+                // Distance to the center grows linearly with the angle to the forward axis
+                let (x, y) = (x * self.aspect, y);
+                let diagonal = (self.aspect.powi(2) + 1.0).sqrt();
+                let r = (x * x + y * y).sqrt();
+                let theta = r / diagonal * fov.to_radians() / 2.0;
+                let (sin_phi, cos_phi) = if r > 0.0 { (-y / r, x / r) } else { (0.0, 0.0) };
+                let direction = self.forward * theta.cos()
+                    + (self.right * cos_phi + self.up * sin_phi) * theta.sin();
+                Ray {
+                    origin: self.origin,
+                    direction: direction.normalize(),
+                }
+            }
+            Projection::Equirectangular => {
+                use std::f64::consts::{FRAC_PI_2, PI};
+                let longitude = x * PI;
+                let latitude = -y * FRAC_PI_2;
+                let direction = (self.forward * longitude.cos() + self.right * longitude.sin())
+                    * latitude.cos()
+                    + self.up * latitude.sin();
+                Ray {
+                    origin: self.origin,
+                    direction: direction.normalize(),
+                }
+            }
+        }
+    }
+    /// Like [`Camera::ray`] but leaving from a point on the lens, so that only objects
+    /// at the focus distance are sharp. `lens` are two numbers from 0 to 1 picking the point.
+    pub fn lens_ray(&self, x: f64, y: f64, lens: (f64, f64)) -> Ray {
+        let pinhole = self.ray(x, y);
+        if self.aperture <= 0.0 || self.projection != Projection::Perspective {
+            return pinhole;
         }
+        // Every ray through the same pixel meets at the focus plane
+        let focus_distance = self.focus_distance();
+        let focus = pinhole.at(focus_distance / pinhole.direction.dot(&self.forward));
+        let (lx, ly) = concentric_disk(lens);
+        let origin = self.origin + (self.right * lx + self.up * ly) * self.aperture;
+        Ray::new_with_from_target(origin, &focus)
+    }
+    /// Same camera for an image with a different width over height ratio
+    pub fn with_aspect(&self, aspect: f64) -> Self {
+        let mut camera = self.clone();
+        camera.aspect = aspect;
+        camera.update();
+        camera
+    }
+    /// Same camera moved to `origin` and looking at `target`
+    pub fn with_view(&self, origin: Vector, target: Vector) -> Self {
+        let mut camera = self.clone();
+        camera.origin = origin;
+        camera.target = target;
+        camera.update();
+        camera
+    }
+    pub fn origin(&self) -> &Vector {
+        &self.origin
+    }
+    pub fn target(&self) -> &Vector {
+        &self.target
+    }
+    /// Up direction the camera was built with, not necessarily perpendicular to `forward`
+    pub fn up_guide(&self) -> &Vector {
+        &self.up_guide
+    }
+    /// Vertical field of view in degrees
+    pub fn fov(&self) -> f64 {
+        self.fov
+    }
+    pub fn aspect(&self) -> f64 {
+        self.aspect
+    }
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+    pub fn aperture(&self) -> f64 {
+        self.aperture
+    }
+    /// Distance from the camera to the plane that is in focus
+    pub fn focus_distance(&self) -> f64 {
+        self.focus_distance
+            .unwrap_or_else(|| (self.target - self.origin).dot(&self.forward))
+    }
+    /// Unit vector the camera is looking along
+    pub fn forward(&self) -> &Vector {
+        &self.forward
+    }
+    /// Unit vector pointing to the top of the image
+    pub fn up(&self) -> &Vector {
+        &self.up
+    }
+    /// Unit vector pointing to the right of the image
+    pub fn right(&self) -> &Vector {
+        &self.right
+    }
+}
+impl Default for Camera {
+    fn default() -> Self {
+        Camera::builder()
+            .build()
+            .expect("the default settings are valid")
+    }
+}
+
+/// What gets stored in a scene file, everything else is derived from it
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct CameraDef {
+    origin: Vector,
+    target: Vector,
+    #[serde(default = "default_up")]
+    up: Vector,
+    fov: f64,
+    #[serde(default)]
+    projection: Projection,
+    #[serde(default)]
+    aperture: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    focus_distance: Option<f64>,
+}
+#[cfg(feature = "serde")]
+fn default_up() -> Vector {
+    YP
+}
+#[cfg(feature = "serde")]
+impl TryFrom<CameraDef> for Camera {
+    type Error = CameraError;
+    fn try_from(def: CameraDef) -> Result<Self, Self::Error> {
+        let builder = Camera::builder()
+            .origin(def.origin)
+            .target(def.target)
+            .up(def.up)
+            .fov(def.fov)
+            .projection(def.projection)
+            .aperture(def.aperture);
+        match def.focus_distance {
+            Some(focus_distance) => builder.focus_distance(focus_distance),
+            None => builder,
+        }
+        .build()
+    }
+}
+#[cfg(feature = "serde")]
+impl From<Camera> for CameraDef {
+    fn from(camera: Camera) -> Self {
+        CameraDef {
+            origin: camera.origin,
+            target: camera.target,
+            up: camera.up_guide,
+            fov: camera.fov,
+            projection: camera.projection,
+            aperture: camera.aperture,
+            focus_distance: camera.focus_distance,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_settings_that_cant_render() {
+        assert!(matches!(
+            Camera::builder().fov(200.0).build(),
+            Err(CameraError::Fov(_))
+        ));
+        assert!(matches!(
+            Camera::builder().fov(f64::NAN).build(),
+            Err(CameraError::Fov(_))
+        ));
+        assert!(matches!(
+            Camera::builder().aspect(0.0).build(),
+            Err(CameraError::Aspect(_))
+        ));
+        assert!(matches!(
+            Camera::builder().aperture(-0.1).build(),
+            Err(CameraError::Aperture(_))
+        ));
+        assert!(Camera::builder().fov(179.0).aperture(0.1).build().is_ok());
     }
 }
@@ -0,0 +1,158 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::shapes::{Object, Shape, Transform};
+
+/// Named set of objects and nested groups placed together, so that something
+/// made of several shapes can be moved as a unit. An [`Instance`](crate::shapes::Instance)
+/// naming the group places another copy of it, on top of the transforms and materials
+/// of the groups it's nested in.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Group {
+    pub name: String,
+    /// Applied on top of the transforms of everything in the group
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Transform::is_identity")
+    )]
+    pub transform: Transform,
+    /// Material used instead of the ones of everything in the group, nested groups included
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub material: Option<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub objects: Vec<Shape>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub groups: Vec<Group>,
+}
+impl Group {
+    pub fn new(name: impl Into<String>) -> Self {
+        Group {
+            name: name.into(),
+            transform: Transform::IDENTITY,
+            material: None,
+            objects: Vec::new(),
+            groups: Vec::new(),
+        }
+    }
+    /// This group or the first one nested in it called `name`
+    pub fn find(&self, name: &str) -> Option<&Group> {
+        self.path_to(name)?.pop()
+    }
+    /// Groups from this one down to the first one nested in it called `name`, both included
+    pub fn path_to(&self, name: &str) -> Option<Vec<&Group>> {
+        if self.name == name {
+            return Some(vec![self]);
+        }
+        let mut path = self.groups.iter().find_map(|group| group.path_to(name))?;
+        path.insert(0, self);
+        Some(path)
+    }
+    /// Every object in the group and its nested groups, with the transforms
+    /// and materials of the groups applied
+    pub fn flatten(&self) -> Vec<Shape> {
+        let mut shapes = self.objects.clone();
+        shapes.extend(self.groups.iter().flat_map(Group::flatten));
+        for shape in &mut shapes {
+            place(shape, &self.transform, self.material.as_deref());
+        }
+        shapes
+    }
+}
+
+/// Moves `shape` into the space of its parent and gives it the parent's material, if any
+pub(super) fn place(shape: &mut Shape, transform: &Transform, material: Option<&str>) {
+    shape.set_transform(*transform * *shape.transform());
+    if let Some(material) = material {
+        shape.set_material(material.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{
+        scene::{Scene, SceneError},
+        shapes::{Instance, Vector},
+    };
+
+    /// `outer` moved along X and painted red, holding `inner` moved along Y with a ball
+    fn nested() -> Group {
+        let mut ball = Shape::new_sphere([0.0, 0.0, 0.0], 1.0);
+        ball.set_material("blue".to_string());
+        let mut inner = Group::new("inner");
+        inner.transform = Transform::translation(Vector::new(0.0, 2.0, 0.0));
+        inner.material = Some("green".to_string());
+        inner.objects.push(ball);
+        let mut outer = Group::new("outer");
+        outer.transform = Transform::translation(Vector::new(1.0, 0.0, 0.0));
+        outer.material = Some("red".to_string());
+        outer.groups.push(inner);
+        outer
+    }
+    fn center(shape: &Shape) -> Vector {
+        shape.transform().point(&Vector::zeros())
+    }
+    fn instance(geometry: &str, offset: Vector) -> Shape {
+        Shape::Instance(Instance::new(geometry, Transform::translation(offset)))
+    }
+
+    #[test]
+    fn flattening_applies_every_group_outermost_last() {
+        let shapes = nested().flatten();
+        assert_eq!(shapes.len(), 1);
+        assert!((center(&shapes[0]) - Vector::new(1.0, 2.0, 0.0)).norm() < 1e-9);
+        assert_eq!(shapes[0].material(), "red");
+    }
+
+    #[test]
+    fn instancing_a_nested_group_keeps_its_parents_placement() {
+        let mut scene = Scene::default();
+        scene.groups.push(nested());
+        scene
+            .objects
+            .push(instance("inner", Vector::new(0.0, 0.0, 3.0)));
+        scene.load(Path::new(".")).unwrap();
+        let shapes = scene.shapes();
+        assert_eq!(shapes.len(), 2);
+        assert!((center(&shapes[0]) - Vector::new(1.0, 2.0, 3.0)).norm() < 1e-9);
+        assert_eq!(shapes[0].material(), "red");
+    }
+
+    #[test]
+    fn loading_twice_changes_nothing() {
+        let mut scene = Scene::default();
+        scene.groups.push(nested());
+        scene.objects.push(instance("outer", Vector::zeros()));
+        scene.load(Path::new(".")).unwrap();
+        scene.load(Path::new(".")).unwrap();
+        assert_eq!(scene.shapes().len(), 2);
+        assert_eq!(scene.objects.len(), 1);
+    }
+
+    #[test]
+    fn rejects_groups_containing_themselves() {
+        let mut inner = Group::new("inner");
+        inner.objects.push(instance("outer", Vector::zeros()));
+        let mut outer = Group::new("outer");
+        outer.groups.push(inner);
+        let mut scene = Scene::default();
+        scene.objects.push(instance("inner", Vector::zeros()));
+        scene.groups.push(outer);
+        assert!(matches!(
+            scene.load(Path::new(".")),
+            Err(SceneError::RecursiveGroup(name)) if name == "outer"
+        ));
+    }
+}
@@ -0,0 +1,263 @@
+use std::fmt::Display;
+
+use image::Pixel;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{color::Color, shapes::Vector};
+
+/// A source of light in the scene
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(tag = "type"))]
+pub enum Light {
+    /// Shines in every direction from a single point, fading with the square of the distance
+    Point {
+        position: Vector,
+        #[cfg_attr(feature = "serde", serde(with = "crate::color::RgbDef"))]
+        color: Color,
+        intensity: f32,
+    },
+    /// Infinitely far away light with parallel rays, like the sun
+    Directional {
+        /// Direction the light travels in
+        direction: Vector,
+        #[cfg_attr(feature = "serde", serde(with = "crate::color::RgbDef"))]
+        color: Color,
+        intensity: f32,
+    },
+    /// Point light restricted to a cone
+    Spot {
+        position: Vector,
+        /// Direction the cone points to
+        direction: Vector,
+        /// Half-angle of the fully lit part of the cone, in degrees
+        inner_angle: f64,
+        /// Half-angle where the light fades to nothing, in degrees
+        outer_angle: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::color::RgbDef"))]
+        color: Color,
+        intensity: f32,
+    },
+    /// Uniform light coming from everywhere, doesn't cast shadows
+    Ambient {
+        #[cfg_attr(feature = "serde", serde(with = "crate::color::RgbDef"))]
+        color: Color,
+        intensity: f32,
+    },
+}
+
+/// A light whose parameters can't describe any actual light
+#[derive(Debug)]
+pub enum LightError {
+    /// A spot light's angles aren't `0 ≤ inner_angle ≤ outer_angle ≤ 90`
+    SpotAngles { inner_angle: f64, outer_angle: f64 },
+    /// A directional or spot light points nowhere
+    ZeroDirection,
+    /// A light's intensity is negative
+    NegativeIntensity(f32),
+}
+impl Display for LightError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LightError::SpotAngles {
+                inner_angle,
+                outer_angle,
+            } => write!(
+                f,
+                "Spot light angles must be 0 ≤ inner ≤ outer ≤ 90 degrees, \
+                 got inner {inner_angle} and outer {outer_angle}"
+            ),
+            LightError::ZeroDirection => write!(f, "Light direction can't be zero"),
+            LightError::NegativeIntensity(intensity) => {
+                write!(f, "Light intensity can't be negative, got {intensity}")
+            }
+        }
+    }
+}
+impl std::error::Error for LightError {}
+
+/// Light arriving at a point from a single [`Light`]
+pub struct Incident {
+    /// Unit vector pointing from the point towards the light
+    pub direction: Vector,
+    /// Distance to the light, infinite for directional lights
+    pub distance: f64,
+    /// Color of the light times its intensity after falloff
+    pub radiance: Color,
+}
+
+impl Light {
+    pub fn point(position: Vector, color: Color, intensity: f32) -> Self {
+        Light::Point {
+            position,
+            color,
+            intensity,
+        }
+    }
+    pub fn ambient(color: Color, intensity: f32) -> Self {
+        Light::Ambient { color, intensity }
+    }
+
+    /// Checks the parameters a scene file could get wrong
+    pub fn validate(&self) -> Result<(), LightError> {
+        let (Light::Point { intensity, .. }
+        | Light::Directional { intensity, .. }
+        | Light::Spot { intensity, .. }
+        | Light::Ambient { intensity, .. }) = *self;
+        if intensity < 0.0 || intensity.is_nan() {
+            return Err(LightError::NegativeIntensity(intensity));
+        }
+        match *self {
+            Light::Directional { direction, .. } | Light::Spot { direction, .. }
+                if direction.norm() == 0.0 || direction.norm().is_nan() =>
+            {
+                Err(LightError::ZeroDirection)
+            }
+            Light::Spot {
+                inner_angle,
+                outer_angle,
+                ..
+            } if !(0.0 <= inner_angle && inner_angle <= outer_angle && outer_angle <= 90.0) => {
+                Err(LightError::SpotAngles {
+                    inner_angle,
+                    outer_angle,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Light reaching `at` from this source, ignoring occluders.
+    /// Returns `None` for ambient lights and points outside a spot light's cone.
+    pub fn incident(&self, at: Vector) -> Option<Incident> {
+        match *self {
+            Light::Point {
+                position,
+                color,
+                intensity,
+            } => {
+                let (direction, distance) = towards(at, position)?;
+                let falloff = intensity / distance.powi(2) as f32;
+                Some(Incident {
+                    direction,
+                    distance,
+                    radiance: color.map(|c| c * falloff),
+                })
+            }
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+            } => Some(Incident {
+                direction: -direction.normalize(),
+                distance: f64::INFINITY,
+                radiance: color.map(|c| c * intensity),
+            }),
+            Light::Spot {
+                position,
+                direction: axis,
+                inner_angle,
+                outer_angle,
+                color,
+                intensity,
+            } => {
+                let (direction, distance) = towards(at, position)?;
+                let cos = -direction.dot(&axis.normalize());
+                let cos_inner = inner_angle.to_radians().cos();
+                let cos_outer = outer_angle.to_radians().cos();
+                if cos <= cos_outer {
+                    return None;
+                }
+                // Smoothly fade out between the inner and outer cones
+                let cone = ((cos - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+                let cone = cone * cone * (3.0 - 2.0 * cone);
+                let falloff = intensity * cone as f32 / distance.powi(2) as f32;
+                Some(Incident {
+                    direction,
+                    distance,
+                    radiance: color.map(|c| c * falloff),
+                })
+            }
+            Light::Ambient { .. } => None,
+        }
+    }
+
+    /// Light added everywhere regardless of geometry
+    pub fn ambient_radiance(&self) -> Option<Color> {
+        match *self {
+            Light::Ambient { color, intensity } => Some(color.map(|c| c * intensity)),
+            _ => None,
+        }
+    }
+}
+
+/// Unit vector and distance from `from` to `to`
+fn towards(from: Vector, to: Vector) -> Option<(Vector, f64)> {
+    let delta = to - from;
+    let distance = delta.norm();
+    if float!(distance -> 0) {
+        return None;
+    }
+    Some((delta / distance, distance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spot(inner_angle: f64, outer_angle: f64) -> Light {
+        Light::Spot {
+            position: Vector::zeros(),
+            direction: Vector::new(0.0, 0.0, 1.0),
+            inner_angle,
+            outer_angle,
+            color: Color::from([1.0, 1.0, 1.0]),
+            intensity: 1.0,
+        }
+    }
+
+    #[test]
+    fn spot_lights_inside_its_cone_only() {
+        let light = spot(20.0, 30.0);
+        assert!(light.validate().is_ok());
+        let ahead = light.incident(Vector::new(0.0, 0.0, 2.0)).unwrap();
+        assert!((ahead.radiance.0[0] - 0.25).abs() < 1e-6);
+        assert!(light.incident(Vector::new(2.0, 0.0, 2.0)).is_none());
+    }
+
+    #[test]
+    fn rejects_bad_spot_angles() {
+        for (inner, outer) in [(30.0, 20.0), (-5.0, 20.0), (20.0, 120.0), (f64::NAN, 20.0)] {
+            assert!(
+                matches!(
+                    spot(inner, outer).validate(),
+                    Err(LightError::SpotAngles { .. })
+                ),
+                "accepted {inner} and {outer}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_lights_pointing_nowhere_or_negative() {
+        let directional = Light::Directional {
+            direction: Vector::zeros(),
+            color: Color::from([1.0, 1.0, 1.0]),
+            intensity: 1.0,
+        };
+        assert!(matches!(
+            directional.validate(),
+            Err(LightError::ZeroDirection)
+        ));
+        let mut light = spot(20.0, 30.0);
+        if let Light::Spot { direction, .. } = &mut light {
+            *direction = Vector::zeros();
+        }
+        assert!(matches!(light.validate(), Err(LightError::ZeroDirection)));
+        let dark = Light::point(Vector::zeros(), Color::from([1.0, 1.0, 1.0]), -1.0);
+        assert!(matches!(
+            dark.validate(),
+            Err(LightError::NegativeIntensity(_))
+        ));
+    }
+}
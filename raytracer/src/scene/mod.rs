@@ -1,117 +1,538 @@
-use fastrand::Rng;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
 use image::{Pixel, Rgb, Rgb32FImage};
 use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    color::Color,
-    shapes::{Intersection, Object, Shape, Vector},
+    accel::Accel,
+    color::{Color, Tonemap, BLACK},
+    denoise::Denoiser,
+    integrator::{Integrator, IntegratorKind},
+    material::Materials,
+    model::{Model, ModelError},
+    sampling::{Sampler, SamplerKind},
+    shapes::{Hit, Instance, Object, Shape},
 };
 
+mod accumulator;
 mod camera;
+mod group;
+mod light;
 mod ray;
+mod target;
 
-pub use camera::Camera;
+pub use accumulator::Accumulator;
+pub use camera::{Camera, CameraBuilder, CameraError, Projection};
+pub use group::Group;
+pub use light::{Incident, Light, LightError};
 pub use ray::Ray;
+pub use target::{parallel_render_target, Gray32FImage, RenderTarget};
+
+use target::Surfaces;
+
+/// Default amount of samples taken for every pixel
+pub const DEFAULT_SAMPLES: u32 = 4;
+/// Default amount of times a ray may bounce off reflective surfaces
+pub const DEFAULT_MAX_DEPTH: u32 = 4;
+
+/// Everything needed to render an image
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct Scene {
+    /// Shapes as written in the scene file, see [`Scene::shapes`] for what gets rendered.
+    /// Call [`Scene::rebuild_accel`] after modifying these once rendering has started.
+    pub objects: Vec<Shape>,
+    /// OBJ files to render along with the objects, see [`Scene::load_models`]
+    pub models: Vec<Model>,
+    /// Named shapes that aren't rendered themselves but placed by
+    /// [`Instance`](crate::shapes::Instance) objects, see [`Scene::resolve_instances`]
+    pub geometry: BTreeMap<String, Shape>,
+    /// Named groups of objects to render along with the objects, see [`Scene::load_groups`]
+    pub groups: Vec<Group>,
+    pub materials: Materials,
+    pub lights: Vec<Light>,
+    pub camera: Camera,
+    /// Color seen by rays that don't hit anything
+    #[cfg_attr(feature = "serde", serde(with = "crate::color::RgbDef"))]
+    pub background: Color,
+    pub settings: RenderSettings,
+    /// Everything [`Scene::load`] got ready to render
+    #[cfg_attr(feature = "serde", serde(skip))]
+    loaded: Option<Vec<Shape>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    accel: OnceLock<Accel>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    emitters: OnceLock<Vec<usize>>,
+}
+/// Something a scene file refers to is missing or broken
+#[derive(Debug)]
+pub enum SceneError {
+    Model(ModelError),
+    Light(LightError),
+    /// An instance names geometry that isn't in the library
+    UnknownGeometry(String),
+    /// Geometry in the library is itself an instance, or combines one with [`Csg`](crate::shapes::Csg)
+    NestedInstance(String),
+    /// A group contains an instance of itself
+    RecursiveGroup(String),
+    /// An operand of a [`Csg`](crate::shapes::Csg) has no inside to combine, like a disk
+    /// or an open mesh
+    FlatOperand(String),
+}
+impl Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneError::Model(e) => write!(f, "{e}"),
+            SceneError::Light(e) => write!(f, "{e}"),
+            SceneError::UnknownGeometry(name) => write!(f, "Unknown geometry `{name}`"),
+            SceneError::NestedInstance(name) => {
+                write!(
+                    f,
+                    "Geometry `{name}` is an instance, which can't be instanced"
+                )
+            }
+            SceneError::RecursiveGroup(name) => write!(f, "Group `{name}` contains itself"),
+            SceneError::FlatOperand(shape) => {
+                write!(f, "{shape} has no inside, it can't be combined with CSG")
+            }
+        }
+    }
+}
+impl std::error::Error for SceneError {}
+impl From<ModelError> for SceneError {
+    fn from(e: ModelError) -> Self {
+        SceneError::Model(e)
+    }
+}
+impl From<LightError> for SceneError {
+    fn from(e: LightError) -> Self {
+        SceneError::Light(e)
+    }
+}
+
+impl Scene {
+    /// Gets the scene ready to render: checks its lights, loads its models, flattens its
+    /// groups and resolves its instances. Relative paths are resolved from `base_dir`.
+    ///
+    /// The result goes to [`Scene::shapes`], leaving what the scene file describes untouched,
+    /// so loading again or saving the scene afterwards doesn't duplicate anything.
+    pub fn load(&mut self, base_dir: &Path) -> Result<(), SceneError> {
+        for light in &self.lights {
+            light.validate()?;
+        }
+        let mut shapes = self.objects.clone();
+        shapes.extend(self.load_models(base_dir)?);
+        shapes.extend(self.load_groups());
+        self.loaded = Some(self.resolve_instances(shapes)?);
+        self.rebuild_accel();
+        Ok(())
+    }
+    /// What gets rendered: everything [`Scene::load`] got ready, or only the objects
+    /// if the scene hasn't been loaded
+    pub fn shapes(&self) -> &[Shape] {
+        self.loaded.as_deref().unwrap_or(&self.objects)
+    }
+    /// Reads every model, giving its meshes and adding its materials to the scene's.
+    /// Relative paths are resolved from `base_dir`, usually the scene file's directory.
+    pub fn load_models(&mut self, base_dir: &Path) -> Result<Vec<Shape>, ModelError> {
+        let mut shapes = Vec::new();
+        for model in &self.models {
+            shapes.extend(model.load(base_dir, &mut self.materials)?);
+        }
+        Ok(shapes)
+    }
+    /// The objects of every group, nested ones included
+    pub fn load_groups(&self) -> Vec<Shape> {
+        self.groups.iter().flat_map(Group::flatten).collect()
+    }
+    /// Points every [`Instance`] in `shapes` at the library geometry it names, which is shared
+    /// rather than copied. Instances naming a group are replaced by a copy of its objects,
+    /// except inside a [`Csg`](crate::shapes::Csg) where they can only name library geometry.
+    pub fn resolve_instances(&self, shapes: Vec<Shape>) -> Result<Vec<Shape>, SceneError> {
+        let mut library = BTreeMap::new();
+        for (name, shape) in &self.geometry {
+            if contains_instance(shape) {
+                return Err(SceneError::NestedInstance(name.clone()));
+            }
+            if let Shape::Csg(csg) = shape {
+                check_solid(&csg.left)?;
+                check_solid(&csg.right)?;
+            }
+            library.insert(name.as_str(), Arc::new(shape.clone()));
+        }
+        let mut resolved = Vec::with_capacity(shapes.len());
+        for obj in shapes {
+            self.resolve_instance(obj, &library, &mut Vec::new(), &mut resolved)?;
+        }
+        Ok(resolved)
+    }
+    /// Adds `obj` to `objects`, resolving it first if it's an instance.
+    /// `groups` are the names of the groups being expanded, to catch groups containing themselves.
+    fn resolve_instance<'s>(
+        &'s self,
+        obj: Shape,
+        library: &BTreeMap<&str, Arc<Shape>>,
+        groups: &mut Vec<&'s str>,
+        objects: &mut Vec<Shape>,
+    ) -> Result<(), SceneError> {
+        let mut instance = match obj {
+            Shape::Instance(instance) => instance,
+            Shape::Csg(mut csg) => {
+                resolve_operand(&mut csg.left, library)?;
+                resolve_operand(&mut csg.right, library)?;
+                objects.push(Shape::Csg(csg));
+                return Ok(());
+            }
+            obj => {
+                objects.push(obj);
+                return Ok(());
+            }
+        };
+        if let Some(shape) = library.get(instance.geometry.as_str()) {
+            instance.set_shape(Arc::clone(shape));
+            objects.push(Shape::Instance(instance));
+            return Ok(());
+        }
+        // The group and the ones it's nested in, placing it like they would
+        let path = self
+            .groups
+            .iter()
+            .find_map(|group| group.path_to(&instance.geometry))
+            .ok_or_else(|| SceneError::UnknownGeometry(instance.geometry.clone()))?;
+        let (group, parents) = path.split_last().expect("paths end at the group");
+        if groups.contains(&group.name.as_str()) {
+            return Err(SceneError::RecursiveGroup(group.name.clone()));
+        }
+        groups.push(&group.name);
+        let Instance {
+            transform,
+            material,
+            ..
+        } = instance;
+        for mut shape in group.flatten() {
+            for parent in parents.iter().rev() {
+                group::place(&mut shape, &parent.transform, parent.material.as_deref());
+            }
+            group::place(&mut shape, &transform, material.as_deref());
+            self.resolve_instance(shape, library, groups, objects)?;
+        }
+        groups.pop();
+        Ok(())
+    }
+    /// Acceleration structure over the [shapes](Scene::shapes), built the first time it's needed
+    pub fn accel(&self) -> &Accel {
+        self.accel.get_or_init(|| Accel::build(self.shapes()))
+    }
+    /// Drops the acceleration structure so it gets rebuilt with the current shapes
+    pub fn rebuild_accel(&mut self) {
+        self.accel = OnceLock::new();
+        self.emitters = OnceLock::new();
+    }
+    /// Indices of the shapes with an emissive material that can be sampled as lights
+    pub fn emitters(&self) -> &[usize] {
+        self.emitters.get_or_init(|| {
+            self.shapes()
+                .iter()
+                .enumerate()
+                .filter(|(_, obj)| {
+                    let emission = self.materials.get(obj.material()).emission;
+                    emission.0.iter().any(|&c| c > 0.0) && obj.area().is_some()
+                })
+                .map(|(i, _)| i)
+                .collect()
+        })
+    }
+    /// Finds the closest object hit by `ray`
+    pub fn closest(&self, ray: &Ray) -> Option<(Hit, &Shape)> {
+        self.closest_before(ray, f64::INFINITY)
+    }
+    /// Finds the closest object hit by `ray` before travelling `t_max`
+    pub fn closest_before(&self, ray: &Ray, t_max: f64) -> Option<(Hit, &Shape)> {
+        let shapes = self.shapes();
+        self.accel()
+            .closest(ray, t_max, shapes)
+            .map(|(hit, i)| (hit, &shapes[i]))
+    }
+    /// Like [`Scene::closest`] but gives the object's index in [`Scene::shapes`]
+    pub fn closest_index(&self, ray: &Ray) -> Option<(Hit, usize)> {
+        self.accel().closest(ray, f64::INFINITY, self.shapes())
+    }
+}
+impl Default for Scene {
+    fn default() -> Self {
+        Scene {
+            objects: Vec::new(),
+            models: Vec::new(),
+            geometry: BTreeMap::new(),
+            groups: Vec::new(),
+            materials: Materials::new(),
+            lights: Vec::new(),
+            camera: Camera::default(),
+            background: BLACK,
+            settings: RenderSettings::default(),
+            loaded: None,
+            accel: OnceLock::new(),
+            emitters: OnceLock::new(),
+        }
+    }
+}
+
+/// Knobs controlling how a scene is rendered and shown
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct RenderSettings {
+    /// How many times a ray may bounce off reflective or transparent surfaces
+    pub max_depth: u32,
+    /// Used by [`render`] and [`parallel_render`]
+    pub integrator: IntegratorKind,
+    /// Samples taken for every pixel, the most taken if adaptive sampling is on
+    pub samples: u32,
+    pub sampler: SamplerKind,
+    /// Stop sampling pixels early once they stop changing
+    pub adaptive: Option<AdaptiveSampling>,
+    /// Filter applied to finished file renders, see [`Denoiser::apply`]
+    pub denoise: Option<Denoiser>,
+    /// Brightness adjustment in stops, every stop doubles the light
+    pub exposure: f32,
+    pub tonemap: Tonemap,
+}
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            max_depth: DEFAULT_MAX_DEPTH,
+            integrator: IntegratorKind::default(),
+            samples: DEFAULT_SAMPLES,
+            sampler: SamplerKind::default(),
+            adaptive: None,
+            denoise: None,
+            exposure: 0.0,
+            tonemap: Tonemap::default(),
+        }
+    }
+}
+impl RenderSettings {
+    /// Turns the linear light of a render into linear values from 0 to 1 ready to be
+    /// encoded for display, applying the exposure and tone mapping
+    pub fn develop(&self, color: Color) -> Color {
+        let scale = self.exposure.exp2();
+        self.tonemap.apply(color.map(|c| c * scale))
+    }
+}
+
+/// Decides when a pixel has enough samples by estimating how noisy its brightness is
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct AdaptiveSampling {
+    /// Samples always taken before checking for convergence
+    pub min_samples: u32,
+    /// Largest standard error allowed, relative to the square root of the brightness
+    /// since the eye notices noise less in bright areas
+    pub threshold: f32,
+}
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        AdaptiveSampling {
+            min_samples: 16,
+            threshold: 0.01,
+        }
+    }
+}
+impl AdaptiveSampling {
+    /// Whether `count` samples whose brightness add up to `sum`, and their squares to
+    /// `sum_squares`, are enough
+    fn converged(&self, count: u32, sum: f32, sum_squares: f32) -> bool {
+        if count < self.min_samples.max(2) {
+            return false;
+        }
+        let n = count as f32;
+        let mean = sum / n;
+        let variance = ((sum_squares - sum * mean) / (n - 1.0)).max(0.0);
+        let error = (variance / n).sqrt();
+        error <= self.threshold * mean.max(0.0).sqrt().max(1e-4)
+    }
+}
 
-const SAMPLES: usize = 4;
+/// Renders with the integrator picked in the scene's settings.
+/// Pixels hold linear light, see [`RenderSettings::develop`] to get displayable colors.
+pub fn render(framebuffer: &mut Rgb32FImage, scene: &Scene) {
+    let integrator = scene.settings.integrator.build(scene);
+    render_with(framebuffer, scene, integrator.as_ref());
+}
 
-pub fn render(framebuffer: &mut Rgb32FImage, objects: &[Shape], camera: &Camera, light: &Vector) {
-    let width: f64 = framebuffer.width() as _;
-    let height: f64 = framebuffer.height() as _;
-    let rng = fastrand::Rng::new();
+pub fn render_with(framebuffer: &mut Rgb32FImage, scene: &Scene, integrator: &dyn Integrator) {
+    let frame = Frame::new(scene, integrator, framebuffer.dimensions());
+    let mut sampler = scene.settings.sampler.build(scene.settings.samples);
 
     for (px, py, pixel) in framebuffer.enumerate_pixels_mut() {
-        *pixel = render_pixel((px, py), (width, height), objects, camera, light, &rng);
+        *pixel = frame.render_pixel((px, py), sampler.as_mut(), 0, None);
     }
 }
 
-pub fn parallel_render(
+/// Renders with the integrator picked in the scene's settings, using every core
+pub fn parallel_render(framebuffer: &mut Rgb32FImage, scene: &Scene) {
+    let integrator = scene.settings.integrator.build(scene);
+    parallel_render_with(framebuffer, scene, integrator.as_ref());
+}
+
+pub fn parallel_render_with(
     framebuffer: &mut Rgb32FImage,
-    objects: &[Shape],
-    camera: &Camera,
-    light: &Vector,
+    scene: &Scene,
+    integrator: &dyn Integrator,
 ) {
-    let width: f64 = framebuffer.width() as _;
-    let height: f64 = framebuffer.height() as _;
+    parallel_render_samples(framebuffer, scene, integrator, 0);
+}
+
+/// Renders the linear light of every pixel, averaging the samples numbered from `first_sample`
+fn parallel_render_samples(
+    framebuffer: &mut Rgb32FImage,
+    scene: &Scene,
+    integrator: &dyn Integrator,
+    first_sample: u32,
+) {
+    let frame = Frame::new(scene, integrator, framebuffer.dimensions());
+    let settings = &scene.settings;
     framebuffer
         .enumerate_pixels_mut()
         .par_bridge()
         .for_each_init(
-            || Rng::new(),
-            |rng, (px, py, pixel)| {
-                *pixel = render_pixel((px, py), (width, height), objects, camera, light, &rng);
+            || settings.sampler.build(settings.samples),
+            |sampler, (px, py, pixel)| {
+                *pixel = frame.render_pixel((px, py), sampler.as_mut(), first_sample, None);
             },
         );
 }
 
-fn render_pixel(
-    (px, py): (u32, u32),
-    (width, height): (f64, f64),
-    objects: &[Shape],
-    camera: &Camera,
-    light: &Vector,
-    rng: &Rng,
-) -> Rgb<f32> {
-    let mut color: Color = Rgb([0., 0., 0.]);
-    let mut dithers = [(0., 0.); SAMPLES + 1];
-    dithers[1..].fill_with(|| (rng.f64() - 0.5, rng.f64() - 0.5));
-    let dithers = dithers;
-
-    for (dx, dy) in dithers {
-        let x = ((px as f64 + dx) / width) * 2.0 - 1.0;
-        let y = ((py as f64 + dy) / height) * 2.0 - 1.0;
-        let ray = camera.ray(x, y);
-        let closest = find_closest(&ray, objects);
-        if let Some((t, obj)) = closest {
-            let at = ray.at(t);
-            let brightness = light_brightness(at, &obj.normal(at), objects, light);
-            debug_assert!(brightness.is_sign_positive(), "brightness = {brightness}");
-            let this_color = obj.color().map(|c| c * brightness);
+/// Everything shared by the pixels of an image being rendered
+struct Frame<'a> {
+    scene: &'a Scene,
+    integrator: &'a dyn Integrator,
+    /// The scene's camera adjusted to the image's aspect ratio
+    camera: Camera,
+    width: f64,
+    height: f64,
+}
+impl<'a> Frame<'a> {
+    fn new(scene: &'a Scene, integrator: &'a dyn Integrator, (width, height): (u32, u32)) -> Self {
+        let (width, height) = (width as f64, height as f64);
+        Frame {
+            scene,
+            integrator,
+            camera: scene.camera.with_aspect(width / height),
+            width,
+            height,
+        }
+    }
+
+    /// Averages the samples of a pixel numbered from `first_sample`,
+    /// recording what their camera rays hit first in `surfaces`
+    fn render_pixel(
+        &self,
+        (px, py): (u32, u32),
+        sampler: &mut dyn Sampler,
+        first_sample: u32,
+        mut surfaces: Option<&mut Surfaces>,
+    ) -> Rgb<f32> {
+        let scene = self.scene;
+        let settings = &scene.settings;
+        let mut color: Color = Rgb([0., 0., 0.]);
+        let mut count = 0;
+        // Brightness statistics for adaptive sampling
+        let (mut sum, mut sum_squares) = (0.0, 0.0);
+
+        for index in 0..settings.samples.max(1) {
+            sampler.start_sample((px, py), first_sample + index);
+            let (dx, dy) = sampler.next_2d();
+            let x = ((px as f64 + dx) / self.width) * 2.0 - 1.0;
+            let y = ((py as f64 + dy) / self.height) * 2.0 - 1.0;
+            let ray = self.camera.lens_ray(x, y, sampler.next_2d());
+            let this_color = match surfaces.as_deref_mut() {
+                Some(surfaces) => {
+                    let (color, first_hit) =
+                        self.integrator.li_with_first_hit(&ray, scene, sampler);
+                    surfaces.add(&ray, first_hit.as_ref(), scene);
+                    color
+                }
+                None => self.integrator.li(&ray, scene, sampler),
+            };
             color.apply2(&this_color, |c1, c2| c1 + c2);
+            count += 1;
+
+            if let Some(adaptive) = &settings.adaptive {
+                let brightness = this_color.to_luma().0[0];
+                sum += brightness;
+                sum_squares += brightness * brightness;
+                if adaptive.converged(count, sum, sum_squares) {
+                    break;
+                }
+            }
         }
+        color.apply(|c| c / count as f32);
+        color
     }
-    color.apply(|c| c / ((SAMPLES + 1) as f32));
-    color.apply(gamma_correction);
-    return color;
 }
 
-fn light_brightness(at: Vector, normal: &Vector, objects: &[Shape], light: &Vector) -> f32 {
-    // Light vector pointing towards `at`
-    let light_ray = Ray::new_with_from_target(at, light);
-    let closest = find_closest(&light_ray, objects);
-    if closest.is_some() {
-        0.0
-    } else {
-        light_ray.direction.dot(&normal).max(0.0) as _
+/// Whether `shape` is an instance or a combination of shapes with one among them
+fn contains_instance(shape: &Shape) -> bool {
+    match shape {
+        Shape::Instance(_) => true,
+        Shape::Csg(csg) => contains_instance(&csg.left) || contains_instance(&csg.right),
+        _ => false,
     }
 }
 
-fn find_closest<'s>(ray: &Ray, objects: &'s [Shape]) -> Option<(f64, &'s Shape)> {
-    let mut closest = None;
-    for obj in objects {
-        if let Intersection::Hit(t) = obj.distance(&ray) {
-            debug_assert!(t.is_finite(), "hit produced an inf");
-            debug_assert!(float!(t > 0), "hit produced a negative {t}");
-            // debug_assert!(t >= 0.0 && t.is_finite(), "t is {t:#?}. Ray {ray} -> {obj}");
-            // Don't collide with self
-            if float!(t -> 0) {
-                continue;
-            }
-            if let Some((closest_t, _)) = closest {
-                if t < closest_t {
-                    closest = Some((t, obj));
-                }
-            } else {
-                closest = Some((t, obj));
-            }
-        }
+/// Whether `shape` has an inside, which flat shapes and open meshes don't
+fn is_solid(shape: &Shape) -> bool {
+    match shape {
+        Shape::Sphere(_)
+        | Shape::Plane(_)
+        | Shape::Cuboid(_)
+        | Shape::Cylinder(_)
+        | Shape::Cone(_)
+        | Shape::Torus(_) => true,
+        Shape::Triangle(_) | Shape::Disk(_) => false,
+        Shape::Mesh(mesh) => mesh.is_closed(),
+        Shape::Instance(instance) => instance.shape().is_some_and(|shape| is_solid(shape)),
+        Shape::Csg(csg) => is_solid(&csg.left) && is_solid(&csg.right),
     }
+}
 
-    return closest;
+/// Rejects operands of a [`Csg`](crate::shapes::Csg) that have no inside, or contain one
+fn check_solid(shape: &Shape) -> Result<(), SceneError> {
+    match shape {
+        Shape::Csg(csg) => {
+            check_solid(&csg.left)?;
+            check_solid(&csg.right)
+        }
+        shape if is_solid(shape) => Ok(()),
+        shape => Err(SceneError::FlatOperand(shape.to_string())),
+    }
 }
 
-fn gamma_correction(channel: f32) -> f32 {
-    const EXP: f32 = 1.0;
-    const GAMMA: f32 = 2.2;
-    (channel * EXP).powf(GAMMA)
+/// Points the instances in an operand of a [`Csg`](crate::shapes::Csg) at their library
+/// geometry, and makes sure the operand has an inside
+fn resolve_operand(
+    shape: &mut Shape,
+    library: &BTreeMap<&str, Arc<Shape>>,
+) -> Result<(), SceneError> {
+    match shape {
+        Shape::Instance(instance) => {
+            let geometry = library
+                .get(instance.geometry.as_str())
+                .ok_or_else(|| SceneError::UnknownGeometry(instance.geometry.clone()))?;
+            instance.set_shape(Arc::clone(geometry));
+        }
+        Shape::Csg(csg) => {
+            resolve_operand(&mut csg.left, library)?;
+            resolve_operand(&mut csg.right, library)?;
+        }
+        _ => {}
+    }
+    check_solid(shape)
 }
@@ -22,6 +22,22 @@ impl Ray {
         let origin = self.at(t);
         Self { origin, direction }
     }
+    /// Bends the ray as it goes through a surface at distance `t` following Snell's law.
+    /// `normal` must face against the ray and `eta` is the ratio of the indices of refraction
+    /// (outside over inside). Returns `None` on total internal reflection.
+    pub fn refract(&self, t: f64, normal: Vector, eta: f64) -> Option<Ray> {
+        let cos_i = -self.direction.dot(&normal);
+        let sin2_t = eta.powi(2) * (1.0 - cos_i.powi(2));
+        if sin2_t > 1.0 {
+            return None;
+        }
+        let cos_t = (1.0 - sin2_t).sqrt();
+        let direction = eta * self.direction + (eta * cos_i - cos_t) * normal;
+        Some(Self {
+            origin: self.at(t),
+            direction: direction.normalize(),
+        })
+    }
 }
 impl core::ops::Sub<Vector> for &Ray {
     type Output = Ray;
@@ -0,0 +1,145 @@
+use image::{ImageBuffer, Luma, Pixel, Rgb, Rgb32FImage};
+use rayon::prelude::*;
+
+use crate::{
+    color::Color,
+    integrator::{FirstHit, Integrator},
+    scene::{Frame, Ray, Scene},
+    shapes::{Object, Vector},
+};
+
+/// Single channel float image
+pub type Gray32FImage = ImageBuffer<Luma<f32>, Vec<f32>>;
+
+/// Beauty image plus buffers describing the first surface seen through every pixel,
+/// averaged over the pixel's samples. Useful for compositing and denoising.
+#[derive(Clone)]
+pub struct RenderTarget {
+    /// Linear light, the same as [`render`](crate::scene::render) gives
+    pub beauty: Rgb32FImage,
+    /// Distance from the camera, infinite where nothing was hit
+    pub depth: Gray32FImage,
+    /// World space unit normals, zero where nothing was hit
+    pub normal: Rgb32FImage,
+    /// Surface colors without lighting, the background where nothing was hit
+    pub albedo: Rgb32FImage,
+    /// Index in [`Scene::shapes`] plus one, zero where nothing was hit
+    pub object_id: ImageBuffer<Luma<u32>, Vec<u32>>,
+    /// Fraction of the light from [`Scene::lights`] that isn't blocked, 0 in full shadow.
+    /// 1 where the integrator casts no shadow rays, like the debug views.
+    pub shadow: Gray32FImage,
+}
+impl RenderTarget {
+    pub fn new(width: u32, height: u32) -> Self {
+        RenderTarget {
+            beauty: Rgb32FImage::new(width, height),
+            depth: Gray32FImage::new(width, height),
+            normal: Rgb32FImage::new(width, height),
+            albedo: Rgb32FImage::new(width, height),
+            object_id: ImageBuffer::new(width, height),
+            shadow: Gray32FImage::new(width, height),
+        }
+    }
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.beauty.dimensions()
+    }
+}
+
+/// Renders the beauty image and every other buffer of `target`, using every core
+pub fn parallel_render_target(
+    target: &mut RenderTarget,
+    scene: &Scene,
+    integrator: &dyn Integrator,
+) {
+    let (w, h) = target.dimensions();
+    let frame = Frame::new(scene, integrator, (w, h));
+    let settings = &scene.settings;
+    let pixels: Vec<(Color, Surfaces)> = (0..w * h)
+        .into_par_iter()
+        .map_init(
+            || settings.sampler.build(settings.samples),
+            |sampler, i| {
+                let mut surfaces = Surfaces::default();
+                let color =
+                    frame.render_pixel((i % w, i / w), sampler.as_mut(), 0, Some(&mut surfaces));
+                (color, surfaces)
+            },
+        )
+        .collect();
+
+    for (i, (color, surfaces)) in pixels.into_iter().enumerate() {
+        let (x, y) = (i as u32 % w, i as u32 / w);
+        target.beauty.put_pixel(x, y, color);
+        surfaces.write(target, x, y);
+    }
+}
+
+/// Running sums of what the camera rays of a pixel hit first, as found by the integrator
+#[derive(Clone, Debug, Default)]
+pub(super) struct Surfaces {
+    samples: u32,
+    hits: u32,
+    depth: f64,
+    normal: Vector,
+    albedo: [f32; 3],
+    /// Object seen by the first sample, averaging IDs makes no sense
+    object_id: u32,
+    shadow: f32,
+    /// Samples that know their shadow, misses and hits the integrator cast shadow rays from
+    shadow_samples: u32,
+}
+impl Surfaces {
+    pub(super) fn add(&mut self, ray: &Ray, first_hit: Option<&FirstHit>, scene: &Scene) {
+        let first_sample = self.samples == 0;
+        self.samples += 1;
+        let first_hit = match first_hit {
+            Some(first_hit) => first_hit,
+            None => {
+                self.albedo = add(self.albedo, scene.background);
+                self.shadow += 1.0;
+                self.shadow_samples += 1;
+                return;
+            }
+        };
+        let obj = &scene.shapes()[first_hit.index];
+        self.hits += 1;
+        self.depth += first_hit.hit.t * ray.direction.norm();
+        self.normal += first_hit.normal;
+        self.albedo = add(self.albedo, scene.materials.get(obj.material()).albedo);
+        if let Some(visibility) = first_hit.visibility {
+            self.shadow += visibility;
+            self.shadow_samples += 1;
+        }
+        if first_sample {
+            self.object_id = first_hit.index as u32 + 1;
+        }
+    }
+
+    fn write(self, target: &mut RenderTarget, x: u32, y: u32) {
+        let samples = self.samples.max(1) as f32;
+        let depth = if self.hits > 0 {
+            (self.depth / self.hits as f64) as f32
+        } else {
+            f32::INFINITY
+        };
+        let normal = self.normal.try_normalize(0.0).unwrap_or_default();
+        target.depth.put_pixel(x, y, Luma([depth]));
+        target
+            .normal
+            .put_pixel(x, y, Rgb([normal.x, normal.y, normal.z].map(|c| c as f32)));
+        target
+            .albedo
+            .put_pixel(x, y, Rgb(self.albedo.map(|c| c / samples)));
+        target.object_id.put_pixel(x, y, Luma([self.object_id]));
+        let shadow = if self.shadow_samples > 0 {
+            self.shadow / self.shadow_samples as f32
+        } else {
+            1.0
+        };
+        target.shadow.put_pixel(x, y, Luma([shadow]));
+    }
+}
+
+fn add(sum: [f32; 3], color: Color) -> [f32; 3] {
+    Rgb(sum).map2(&color, |a, b| a + b).0
+}
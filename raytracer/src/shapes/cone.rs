@@ -0,0 +1,216 @@
+use std::fmt::Display;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accel::Aabb,
+    material::DEFAULT_MATERIAL,
+    scene::Ray,
+    shapes::{solve_quadratic, Intersection, Interval, Object, Shape, Transform, Vector},
+};
+
+/// Cone closed by a flat cap at its base
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Cone {
+    /// Center of the base
+    pub origin: Vector,
+    /// Direction from the base to the tip
+    pub axis: Vector,
+    /// Radius of the base
+    pub radius: f64,
+    pub height: f64,
+    /// Name of the [`Material`](crate::material::Material) used to shade this shape
+    #[cfg_attr(feature = "serde", serde(default = "crate::shapes::default_material"))]
+    pub material: String,
+    /// Applied on top of the other fields
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Transform::is_identity")
+    )]
+    pub transform: Transform,
+}
+impl Cone {
+    pub fn new(origin: Vector, axis: Vector, radius: f64, height: f64) -> Self {
+        debug_assert!(radius > 0.0 && height > 0.0);
+        Cone {
+            origin,
+            axis: axis.normalize(),
+            radius,
+            height,
+            material: DEFAULT_MATERIAL.to_string(),
+            transform: Transform::IDENTITY,
+        }
+    }
+    pub fn new_with_material(
+        origin: Vector,
+        axis: Vector,
+        radius: f64,
+        height: f64,
+        material: &str,
+    ) -> Self {
+        let this = Cone::new(origin, axis, radius, height);
+        Cone {
+            material: material.to_string(),
+            ..this
+        }
+    }
+    /// Takes the cone from standing on the origin along Z into the scene
+    fn frame(&self) -> Transform {
+        self.transform * Transform::from_axis(self.origin, self.axis)
+    }
+    /// How much the radius shrinks for every unit up the axis
+    fn slope(&self) -> f64 {
+        self.radius / self.height
+    }
+    /// Every distance at which the ray crosses the surface, in no particular order
+    fn crossings(&self, ray: &Ray) -> Vec<f64> {
+        let ray = self.frame().ray_to_local(ray);
+        let (o, d) = (&ray.origin, &ray.direction);
+        let k = self.slope();
+        let mut crossings = Vec::with_capacity(2);
+        /*
+          Side: x² + y² = (r - kz)², for 0 ≤ z ≤ h.
+          Substituting the ray and grouping by powers of t:
+            a = dx² + dy² - k²dz²
+            b = 2(ox dx + oy dy + k dz (r - k oz))
+            c = ox² + oy² - (r - k oz)²
+        */
+        let rim = self.radius - k * o.z;
+        if let Some((t0, t1)) = solve_quadratic(
+            d.x * d.x + d.y * d.y - (k * d.z).powi(2),
+            2.0 * (o.x * d.x + o.y * d.y + k * d.z * rim),
+            o.x * o.x + o.y * o.y - rim.powi(2),
+        ) {
+            // The equation also describes the mirrored cone above the tip
+            let on_cone = |t: f64| (0.0..=self.height).contains(&(o.z + d.z * t));
+            crossings.extend([t0, t1].into_iter().filter(|&t| on_cone(t)));
+        }
+        // Base: z = 0
+        if d.z != 0.0 {
+            let t = -o.z / d.z;
+            if (o.xy() + d.xy() * t).norm_squared() <= self.radius.powi(2) {
+                crossings.push(t);
+            }
+        }
+        crossings
+    }
+}
+impl Object for Cone {
+    fn distance(&self, ray: &Ray) -> Intersection {
+        Intersection::nearest(self.crossings(ray))
+    }
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        Interval::hull(&self.crossings(ray))
+    }
+    fn normal(&self, point: Vector) -> Vector {
+        let frame = self.frame();
+        let p = frame.point_to_local(&point);
+        let k = self.slope();
+        let rho = p.xy().norm();
+        // Distance to the side, measured perpendicular to it
+        let side = (rho - (self.radius - k * p.z)).abs() / (1.0 + k * k).sqrt();
+        let normal = if rho == 0.0 {
+            // On the axis, either the middle of the base or right at the tip
+            Vector::new(0.0, 0.0, (p.z - self.height / 2.0).signum())
+        } else if p.z.abs() < side {
+            Vector::new(0.0, 0.0, -1.0)
+        } else {
+            Vector::new(p.x / rho, p.y / rho, k)
+        };
+        frame.normal(&normal)
+    }
+    fn bounds(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(self.frame().bounds(&Aabb::new(
+            Vector::new(-r, -r, 0.0),
+            Vector::new(r, r, self.height),
+        )))
+    }
+    fn material(&self) -> &str {
+        &self.material
+    }
+    fn set_material(&mut self, material: String) {
+        self.material = material;
+    }
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+    fn into_shape(self) -> Shape {
+        Shape::Cone(self)
+    }
+    fn pos(&self) -> &Vector {
+        &self.origin
+    }
+    fn set_pos(&mut self, pos: Vector) {
+        self.origin = pos;
+    }
+}
+impl Display for Cone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Cone of radius {r} from ({ox}, {oy}, {oz}) to ({ex}, {ey}, {ez})",
+            r = self.radius,
+            ox = self.origin[0],
+            oy = self.origin[1],
+            oz = self.origin[2],
+            ex = self.origin[0] + self.axis[0] * self.height,
+            ey = self.origin[1] + self.axis[1] * self.height,
+            ez = self.origin[2] + self.axis[2] * self.height,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::test_util::{assert_bounds, assert_hit, assert_intervals, assert_miss, ray};
+
+    /// Base of radius 1 on the XY plane, tip at z = 1
+    fn cone() -> Cone {
+        Cone::new(Vector::zeros(), Vector::new(0.0, 0.0, 1.0), 1.0, 1.0)
+    }
+
+    #[test]
+    fn hits_the_side_halfway_up() {
+        // At z = 0.5 the radius is 0.5
+        let ray = ray([-5.0, 0.0, 0.5], [1.0, 0.0, 0.0]);
+        assert_hit(&cone(), &ray, 4.5, Vector::new(-1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn hits_the_base() {
+        let ray = ray([0.5, 0.0, -2.0], [0.0, 0.0, 1.0]);
+        assert_hit(&cone(), &ray, 2.0, Vector::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn hits_the_side_from_above() {
+        let ray = ray([0.5, 0.0, 5.0], [0.0, 0.0, -1.0]);
+        assert_hit(&cone(), &ray, 4.5, Vector::new(1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn misses_the_mirrored_cone_above_the_tip() {
+        assert_miss(&cone(), &ray([-5.0, 0.0, 1.5], [1.0, 0.0, 0.0]));
+        assert_miss(&cone(), &ray([-5.0, 0.0, -0.5], [1.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn inside_between_the_side_and_the_base() {
+        let across = ray([-5.0, 0.0, 0.5], [1.0, 0.0, 0.0]);
+        assert_intervals(&cone().intervals(&across), &[(4.5, 5.5)]);
+        let up = ray([0.5, 0.0, -2.0], [0.0, 0.0, 1.0]);
+        assert_intervals(&cone().intervals(&up), &[(2.0, 2.5)]);
+    }
+
+    #[test]
+    fn bounds_go_from_the_base_to_the_tip() {
+        assert_bounds(&cone(), [-1.0, -1.0, 0.0], [1.0, 1.0, 1.0]);
+    }
+}
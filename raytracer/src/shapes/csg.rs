@@ -0,0 +1,441 @@
+use std::fmt::Display;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accel::Aabb,
+    material::DEFAULT_MATERIAL,
+    scene::Ray,
+    shapes::{Hit, Intersection, Interval, Object, Shape, Transform, Vector},
+    MIN_T,
+};
+
+/// How the insides of the two operands of a [`Csg`] are combined
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CsgOperation {
+    /// Inside either operand
+    #[default]
+    Union,
+    /// Inside both operands
+    Intersection,
+    /// Inside the left operand but not the right one
+    Difference,
+}
+impl CsgOperation {
+    fn keeps(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+    /// Stretches of the line inside the combination of the operands' stretches
+    pub fn combine(self, left: &[Interval], right: &[Interval]) -> Vec<Interval> {
+        let untagged = |intervals: &[Interval]| -> Vec<_> {
+            intervals
+                .iter()
+                .map(|&interval| (interval, (), ()))
+                .collect()
+        };
+        self.combine_tagged(&untagged(left), &untagged(right))
+            .into_iter()
+            .map(|(interval, ..)| interval)
+            .collect()
+    }
+    /// Like [`CsgOperation::combine`], with something attached to where each stretch
+    /// goes in and comes out, which is carried over to the combined stretches' ends
+    fn combine_tagged<T: Copy>(
+        self,
+        left: &[(Interval, T, T)],
+        right: &[(Interval, T, T)],
+    ) -> Vec<(Interval, T, T)> {
+        // (distance, whether it's the left operand, whether the line goes in, tag)
+        let mut events = Vec::with_capacity(2 * (left.len() + right.len()));
+        for (intervals, is_left) in [(left, true), (right, false)] {
+            for &(interval, enter, exit) in intervals {
+                events.push((interval.enter, is_left, true, enter));
+                events.push((interval.exit, is_left, false, exit));
+            }
+        }
+        // Going in before coming out at the same spot, so touching stretches merge
+        events.sort_by(|a, b| a.0.total_cmp(&b.0).then(b.2.cmp(&a.2)));
+        let (mut in_left, mut in_right) = (false, false);
+        let mut enter = None;
+        let mut combined = Vec::new();
+        for (t, is_left, entering, tag) in events {
+            if is_left {
+                in_left = entering;
+            } else {
+                in_right = entering;
+            }
+            match (enter, self.keeps(in_left, in_right)) {
+                (None, true) => enter = Some((t, tag)),
+                (Some((start, start_tag)), false) => {
+                    if start < t {
+                        let interval = Interval {
+                            enter: start,
+                            exit: t,
+                        };
+                        combined.push((interval, start_tag, tag));
+                    }
+                    enter = None;
+                }
+                _ => {}
+            }
+        }
+        combined
+    }
+}
+
+/// Constructive solid geometry: two shapes combined into one by their insides.
+///
+/// Only shapes with an inside, as given by [`Object::intervals`], can be combined,
+/// [`Scene::load`](crate::scene::Scene::load) rejects flat shapes and open meshes.
+/// The whole result is shaded with its own material, not the operands'.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Csg {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub operation: CsgOperation,
+    pub left: Box<Shape>,
+    pub right: Box<Shape>,
+    /// Name of the [`Material`](crate::material::Material) used to shade this shape
+    #[cfg_attr(feature = "serde", serde(default = "crate::shapes::default_material"))]
+    pub material: String,
+    /// Applied on top of the operands' own transforms
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Transform::is_identity")
+    )]
+    pub transform: Transform,
+}
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Shape, right: Shape) -> Self {
+        Csg {
+            operation,
+            left: Box::new(left),
+            right: Box::new(right),
+            material: DEFAULT_MATERIAL.to_string(),
+            transform: Transform::IDENTITY,
+        }
+    }
+    pub fn new_with_material(
+        operation: CsgOperation,
+        left: Shape,
+        right: Shape,
+        material: &str,
+    ) -> Self {
+        let this = Csg::new(operation, left, right);
+        Csg {
+            material: material.to_string(),
+            ..this
+        }
+    }
+    /// Like [`Object::intervals`], along with the operand whose surface is at each end.
+    /// Operands are numbered from the left starting at `first`, going into nested combinations.
+    fn boundaries(&self, ray: &Ray, first: usize) -> Vec<(Interval, usize, usize)> {
+        let ray = self.transform.ray_to_local(ray);
+        let left = operand_boundaries(&self.left, &ray, first);
+        let right = operand_boundaries(&self.right, &ray, first + operand_count(&self.left));
+        self.operation.combine_tagged(&left, &right)
+    }
+    /// Normal of the operand numbered `operand` at `point`, facing out of the combination
+    fn operand_normal(&self, point: Vector, operand: usize) -> Vector {
+        let point = self.transform.point_to_local(&point);
+        let left_count = operand_count(&self.left);
+        let normal = if operand < left_count {
+            operand_normal(&self.left, point, operand)
+        } else {
+            let normal = operand_normal(&self.right, point, operand - left_count);
+            if self.operation == CsgOperation::Difference {
+                // The right operand's surface faces into the hole it carves
+                -normal
+            } else {
+                normal
+            }
+        };
+        self.transform.normal(&normal)
+    }
+}
+impl Object for Csg {
+    fn distance(&self, ray: &Ray) -> Intersection {
+        match self.hit(ray) {
+            Some(hit) => Intersection::Hit(hit.t),
+            None => Intersection::Miss,
+        }
+    }
+    /// Remembers which operand's surface was hit
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        self.boundaries(ray, 0)
+            .into_iter()
+            .flat_map(|(interval, enter, exit)| [(interval.enter, enter), (interval.exit, exit)])
+            .filter(|&(t, _)| t > MIN_T && t.is_finite())
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(t, operand)| Hit {
+                t,
+                part: operand,
+                uv: (0.0, 0.0),
+            })
+    }
+    fn hit_normal(&self, ray: &Ray, hit: &Hit) -> Vector {
+        self.operand_normal(ray.at(hit.t), hit.part)
+    }
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        self.boundaries(ray, 0)
+            .into_iter()
+            .map(|(interval, ..)| interval)
+            .collect()
+    }
+    fn normal(&self, point: Vector) -> Vector {
+        // Without a `Hit`, use the surface crossed closest to the point along the axes
+        let closest = [Vector::x(), Vector::y(), Vector::z()]
+            .into_iter()
+            .flat_map(|direction| {
+                let probe = Ray {
+                    origin: point,
+                    direction,
+                };
+                self.boundaries(&probe, 0)
+            })
+            .flat_map(|(interval, enter, exit)| [(interval.enter, enter), (interval.exit, exit)])
+            .filter(|(t, _)| t.is_finite())
+            .min_by(|a, b| a.0.abs().total_cmp(&b.0.abs()));
+        match closest {
+            Some((_, operand)) => self.operand_normal(point, operand),
+            // Shouldn't happen for points obtained from `distance`
+            None => Vector::zeros(),
+        }
+    }
+    fn bounds(&self) -> Option<Aabb> {
+        let (left, right) = (self.left.bounds(), self.right.bounds());
+        let bounds = match self.operation {
+            CsgOperation::Union => left?.union(&right?),
+            CsgOperation::Intersection => match (left, right) {
+                (Some(left), Some(right)) => Aabb {
+                    min: left.min.sup(&right.min),
+                    max: left.max.inf(&right.max),
+                },
+                (bounds, None) | (None, bounds) => bounds?,
+            },
+            CsgOperation::Difference => left?,
+        };
+        Some(self.transform.bounds(&bounds))
+    }
+    fn material(&self) -> &str {
+        &self.material
+    }
+    fn set_material(&mut self, material: String) {
+        self.material = material;
+    }
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+    fn into_shape(self) -> Shape {
+        Shape::Csg(self)
+    }
+    fn pos(&self) -> &Vector {
+        self.transform.offset()
+    }
+    fn set_pos(&mut self, pos: Vector) {
+        self.transform.set_offset(pos);
+    }
+}
+/// How many operands `shape` counts as, going into nested combinations
+fn operand_count(shape: &Shape) -> usize {
+    match shape {
+        Shape::Csg(csg) => operand_count(&csg.left) + operand_count(&csg.right),
+        _ => 1,
+    }
+}
+
+/// [`Csg::boundaries`] of an operand numbered `number`, or numbered from it if it's a combination
+fn operand_boundaries(shape: &Shape, ray: &Ray, number: usize) -> Vec<(Interval, usize, usize)> {
+    match shape {
+        Shape::Csg(csg) => csg.boundaries(ray, number),
+        shape => shape
+            .intervals(ray)
+            .into_iter()
+            .map(|interval| (interval, number, number))
+            .collect(),
+    }
+}
+
+/// [`Csg::operand_normal`] within an operand
+fn operand_normal(shape: &Shape, point: Vector, operand: usize) -> Vector {
+    match shape {
+        Shape::Csg(csg) => csg.operand_normal(point, operand),
+        shape => shape.normal(point),
+    }
+}
+
+impl Display for Csg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let operation = match self.operation {
+            CsgOperation::Union => "∪",
+            CsgOperation::Intersection => "∩",
+            CsgOperation::Difference => "∖",
+        };
+        write!(f, "({}) {operation} ({})", self.left, self.right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{
+        scene::{Scene, SceneError},
+        shapes::{
+            test_util::{assert_hit, assert_intervals, ray},
+            Instance, Mesh,
+        },
+    };
+
+    /// Unit spheres centered at x = -0.5 and x = 0.5
+    fn csg(operation: CsgOperation) -> Csg {
+        Csg::new(
+            operation,
+            Shape::new_sphere([-0.5, 0.0, 0.0], 1.0),
+            Shape::new_sphere([0.5, 0.0, 0.0], 1.0),
+        )
+    }
+    fn along_x() -> Ray {
+        ray([-5.0, 0.0, 0.0], [1.0, 0.0, 0.0])
+    }
+
+    #[test]
+    fn combines_intervals() {
+        let ray = along_x();
+        assert_intervals(&csg(CsgOperation::Union).intervals(&ray), &[(3.5, 6.5)]);
+        assert_intervals(
+            &csg(CsgOperation::Intersection).intervals(&ray),
+            &[(4.5, 5.5)],
+        );
+        assert_intervals(
+            &csg(CsgOperation::Difference).intervals(&ray),
+            &[(3.5, 4.5)],
+        );
+    }
+
+    #[test]
+    fn difference_can_split_the_left_operand() {
+        let long = Interval {
+            enter: 0.0,
+            exit: 10.0,
+        };
+        let hole = Interval {
+            enter: 4.0,
+            exit: 6.0,
+        };
+        let got = CsgOperation::Difference.combine(&[long], &[hole]);
+        assert_intervals(&got, &[(0.0, 4.0), (6.0, 10.0)]);
+    }
+
+    #[test]
+    fn difference_faces_into_the_hole() {
+        let csg = csg(CsgOperation::Difference);
+        assert_hit(&csg, &along_x(), 3.5, Vector::new(-1.0, 0.0, 0.0));
+        // Leaves through the right sphere's surface, facing back towards the ray
+        let inside = ray([-1.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        assert_hit(&csg, &inside, 0.5, Vector::new(1.0, 0.0, 0.0));
+        let normal = csg.normal(Vector::new(-0.5, 0.0, 0.0));
+        assert!(
+            (normal - Vector::new(1.0, 0.0, 0.0)).norm() < 1e-9,
+            "{normal}"
+        );
+    }
+
+    #[test]
+    fn nested_operands_keep_their_normals() {
+        // Both spheres, with a slab carved out of the middle
+        let slab = Shape::new_cuboid([-0.25, -2.0, -2.0], [0.25, 2.0, 2.0]);
+        let csg = Csg::new(
+            CsgOperation::Difference,
+            Shape::Csg(csg(CsgOperation::Union)),
+            slab,
+        );
+        assert_intervals(&csg.intervals(&along_x()), &[(3.5, 4.75), (5.25, 6.5)]);
+        let from = |x: f64| ray([x, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        assert_hit(&csg, &from(-5.0), 3.5, Vector::new(-1.0, 0.0, 0.0));
+        assert_hit(&csg, &from(-1.0), 0.75, Vector::new(1.0, 0.0, 0.0));
+        assert_hit(&csg, &from(0.0), 0.25, Vector::new(-1.0, 0.0, 0.0));
+        assert_hit(&csg, &from(1.0), 0.5, Vector::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn follows_its_transform() {
+        let mut csg = csg(CsgOperation::Difference);
+        // Along Y and twice as big, so the spheres are centered at y = -1 and y = 1
+        csg.transform = Transform::new(
+            Vector::new(0.0, 0.0, 10.0),
+            Vector::new(0.0, 0.0, 90.0),
+            Vector::repeat(2.0),
+        )
+        .unwrap();
+        let up = ray([0.0, -5.0, 10.0], [0.0, 1.0, 0.0]);
+        assert_intervals(&csg.intervals(&up), &[(2.0, 4.0)]);
+        let inside = ray([0.0, -2.0, 10.0], [0.0, 1.0, 0.0]);
+        assert_hit(&csg, &inside, 1.0, Vector::new(0.0, 1.0, 0.0));
+        let across = ray([-5.0, -2.0, 10.0], [1.0, 0.0, 0.0]);
+        let side = 5.0 - 3f64.sqrt();
+        assert_hit(&csg, &across, side, Vector::new(-3f64.sqrt(), -1.0, 0.0));
+    }
+
+    #[test]
+    fn starts_inside() {
+        let csg = csg(CsgOperation::Union);
+        let ray = Ray {
+            origin: Vector::zeros(),
+            direction: Vector::new(0.0, 1.0, 0.0),
+        };
+        let expected = 0.75f64.sqrt();
+        assert!(matches!(csg.distance(&ray), Intersection::Hit(t) if (t - expected).abs() < 1e-9));
+    }
+
+    #[test]
+    fn scenes_reject_flat_operands() {
+        let sphere = Shape::new_sphere([0.0, 0.0, 0.0], 1.0);
+        let disk = Shape::new_disk([0.0, 0.0, 0.0], [0.0, 0.0, 1.0], 1.0);
+        let mut scene = Scene::default();
+        let flat = Csg::new(CsgOperation::Difference, sphere.clone(), disk);
+        scene.objects.push(Shape::Csg(flat));
+        assert!(matches!(
+            scene.load(Path::new(".")),
+            Err(SceneError::FlatOperand(_))
+        ));
+
+        // Also when the flat shape comes from the library, here an open mesh
+        let square = Mesh::new(
+            vec![
+                Vector::new(0.0, 0.0, 0.0),
+                Vector::new(1.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            ],
+            Vec::new(),
+            vec![[0, 1, 2]],
+        )
+        .unwrap();
+        let mut scene = Scene::default();
+        scene
+            .geometry
+            .insert("square".to_string(), Shape::Mesh(square));
+        let instance = Shape::Instance(Instance::new("square", Transform::IDENTITY));
+        let nested = Csg::new(CsgOperation::Union, sphere.clone(), instance);
+        let outer = Csg::new(CsgOperation::Intersection, Shape::Csg(nested), sphere);
+        scene.objects.push(Shape::Csg(outer));
+        assert!(matches!(
+            scene.load(Path::new(".")),
+            Err(SceneError::FlatOperand(_))
+        ));
+    }
+}
@@ -0,0 +1,200 @@
+use std::fmt::Display;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accel::Aabb,
+    material::DEFAULT_MATERIAL,
+    scene::Ray,
+    shapes::{Intersection, Interval, Object, Shape, Transform, Vector},
+};
+
+/// Box between two corners, with its faces along the axes before the transform is applied
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Cuboid {
+    pub min: Vector,
+    pub max: Vector,
+    /// Name of the [`Material`](crate::material::Material) used to shade this shape
+    #[cfg_attr(feature = "serde", serde(default = "crate::shapes::default_material"))]
+    pub material: String,
+    /// Applied on top of the other fields
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Transform::is_identity")
+    )]
+    pub transform: Transform,
+}
+impl Cuboid {
+    /// Box with `a` and `b` as opposite corners
+    pub fn new(a: Vector, b: Vector) -> Self {
+        Cuboid {
+            min: a.inf(&b),
+            max: a.sup(&b),
+            material: DEFAULT_MATERIAL.to_string(),
+            transform: Transform::IDENTITY,
+        }
+    }
+    pub fn new_with_material(a: Vector, b: Vector, material: &str) -> Self {
+        let this = Cuboid::new(a, b);
+        Cuboid {
+            material: material.to_string(),
+            ..this
+        }
+    }
+    /// Distances at which the ray enters and leaves the box, the entry may be behind it
+    fn slabs(&self, ray: &Ray) -> Option<(f64, f64)> {
+        /*
+          The box is the space between three pairs of parallel planes, the ray is inside it
+          from the last plane it goes through towards the box to the first one it leaves by
+        */
+        let mut near = f64::NEG_INFINITY;
+        let mut far = f64::INFINITY;
+        for axis in 0..3 {
+            let inv = 1.0 / ray.direction[axis];
+            let t0 = (self.min[axis] - ray.origin[axis]) * inv;
+            let t1 = (self.max[axis] - ray.origin[axis]) * inv;
+            // Parallel rays give NaN when starting right on a face, treat them as inside
+            let (t0, t1) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+            if !t0.is_nan() {
+                near = near.max(t0);
+            }
+            if !t1.is_nan() {
+                far = far.min(t1);
+            }
+        }
+        (near <= far).then_some((near, far))
+    }
+}
+impl Object for Cuboid {
+    fn distance(&self, ray: &Ray) -> Intersection {
+        let ray = self.transform.ray_to_local(ray);
+        match self.slabs(&ray) {
+            Some((near, far)) => Intersection::nearest([near, far]),
+            None => Intersection::Miss,
+        }
+    }
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let ray = self.transform.ray_to_local(ray);
+        match self.slabs(&ray) {
+            Some((enter, exit)) if enter < exit => vec![Interval { enter, exit }],
+            _ => Vec::new(),
+        }
+    }
+    fn normal(&self, point: Vector) -> Vector {
+        // The face the point is on is the one it's closest to, relative to the box's size
+        let point = self.transform.point_to_local(&point);
+        let center = (self.min + self.max) / 2.0;
+        let half = (self.max - self.min) / 2.0;
+        let offset = (point - center).component_div(&half.map(|h| h.max(f64::EPSILON)));
+        let axis = offset.iamax();
+        let mut normal = Vector::zeros();
+        normal[axis] = offset[axis].signum();
+        self.transform.normal(&normal)
+    }
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.transform.bounds(&Aabb::new(self.min, self.max)))
+    }
+    fn material(&self) -> &str {
+        &self.material
+    }
+    fn set_material(&mut self, material: String) {
+        self.material = material;
+    }
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+    fn into_shape(self) -> Shape {
+        Shape::Cuboid(self)
+    }
+    fn pos(&self) -> &Vector {
+        &self.min
+    }
+    fn set_pos(&mut self, pos: Vector) {
+        let offset = pos - self.min;
+        self.min += offset;
+        self.max += offset;
+    }
+}
+impl Display for Cuboid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{x0}, {x1}]×[{y0}, {y1}]×[{z0}, {z1}]",
+            x0 = self.min[0],
+            x1 = self.max[0],
+            y0 = self.min[1],
+            y1 = self.max[1],
+            z0 = self.min[2],
+            z1 = self.max[2],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::test_util::{assert_bounds, assert_hit, assert_intervals, assert_miss, ray};
+
+    fn unit_box() -> Cuboid {
+        Cuboid::new(Vector::new(-1.0, -1.0, -1.0), Vector::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn hits_the_near_face() {
+        let ray = ray([0.2, 0.3, -5.0], [0.0, 0.0, 1.0]);
+        assert_hit(&unit_box(), &ray, 4.0, Vector::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn leaves_through_the_far_face_from_inside() {
+        let ray = ray([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        assert_hit(&unit_box(), &ray, 1.0, Vector::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn misses_beside_and_behind() {
+        assert_miss(&unit_box(), &ray([2.0, 0.0, -5.0], [0.0, 0.0, 1.0]));
+        assert_miss(&unit_box(), &ray([0.0, 0.0, 5.0], [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn follows_its_transform() {
+        let mut cuboid = unit_box();
+        cuboid.transform = Transform::new(
+            Vector::new(10.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 45.0),
+            Vector::repeat(1.0),
+        )
+        .unwrap();
+        // Rotated by 45°, the edge along Z now points towards the ray
+        let ray = ray([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        let expected = 10.0 - 2f64.sqrt();
+        assert!(
+            matches!(cuboid.distance(&ray), Intersection::Hit(t) if (t - expected).abs() < 1e-9)
+        );
+        let reach = 2f64.sqrt();
+        assert_bounds(
+            &cuboid,
+            [10.0 - reach, -reach, -1.0],
+            [10.0 + reach, reach, 1.0],
+        );
+    }
+
+    #[test]
+    fn inside_between_opposite_faces() {
+        let through = ray([-5.0, 0.5, 0.5], [1.0, 0.0, 0.0]);
+        assert_intervals(&unit_box().intervals(&through), &[(4.0, 6.0)]);
+        let beside = ray([-5.0, 2.0, 0.0], [1.0, 0.0, 0.0]);
+        assert_intervals(&unit_box().intervals(&beside), &[]);
+    }
+
+    #[test]
+    fn bounds_are_the_corners() {
+        assert_bounds(&unit_box(), [-1.0, -1.0, -1.0], [1.0, 1.0, 1.0]);
+    }
+}
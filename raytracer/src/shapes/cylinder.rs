@@ -0,0 +1,205 @@
+use std::fmt::Display;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accel::Aabb,
+    material::DEFAULT_MATERIAL,
+    scene::Ray,
+    shapes::{solve_quadratic, Intersection, Interval, Object, Shape, Transform, Vector},
+};
+
+/// Cylinder closed by a flat cap at each end
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Cylinder {
+    /// Center of the bottom cap
+    pub origin: Vector,
+    /// Direction from the bottom cap to the top one
+    pub axis: Vector,
+    pub radius: f64,
+    pub height: f64,
+    /// Name of the [`Material`](crate::material::Material) used to shade this shape
+    #[cfg_attr(feature = "serde", serde(default = "crate::shapes::default_material"))]
+    pub material: String,
+    /// Applied on top of the other fields
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Transform::is_identity")
+    )]
+    pub transform: Transform,
+}
+impl Cylinder {
+    pub fn new(origin: Vector, axis: Vector, radius: f64, height: f64) -> Self {
+        debug_assert!(radius > 0.0 && height > 0.0);
+        Cylinder {
+            origin,
+            axis: axis.normalize(),
+            radius,
+            height,
+            material: DEFAULT_MATERIAL.to_string(),
+            transform: Transform::IDENTITY,
+        }
+    }
+    pub fn new_with_material(
+        origin: Vector,
+        axis: Vector,
+        radius: f64,
+        height: f64,
+        material: &str,
+    ) -> Self {
+        let this = Cylinder::new(origin, axis, radius, height);
+        Cylinder {
+            material: material.to_string(),
+            ..this
+        }
+    }
+    /// Takes the cylinder from standing on the origin along Z into the scene
+    fn frame(&self) -> Transform {
+        self.transform * Transform::from_axis(self.origin, self.axis)
+    }
+    /// Every distance at which the ray crosses the surface, in no particular order
+    fn crossings(&self, ray: &Ray) -> Vec<f64> {
+        let ray = self.frame().ray_to_local(ray);
+        let (o, d) = (&ray.origin, &ray.direction);
+        let mut crossings = Vec::with_capacity(2);
+        let inside_height = |t: f64| (0.0..=self.height).contains(&(o.z + d.z * t));
+        // Side: x² + y² = r²
+        if let Some((t0, t1)) = solve_quadratic(
+            d.x * d.x + d.y * d.y,
+            2.0 * (o.x * d.x + o.y * d.y),
+            o.x * o.x + o.y * o.y - self.radius.powi(2),
+        ) {
+            crossings.extend([t0, t1].into_iter().filter(|&t| inside_height(t)));
+        }
+        // Caps: z = 0 and z = h
+        if d.z != 0.0 {
+            for z in [0.0, self.height] {
+                let t = (z - o.z) / d.z;
+                if (o.xy() + d.xy() * t).norm_squared() <= self.radius.powi(2) {
+                    crossings.push(t);
+                }
+            }
+        }
+        crossings
+    }
+}
+impl Object for Cylinder {
+    fn distance(&self, ray: &Ray) -> Intersection {
+        Intersection::nearest(self.crossings(ray))
+    }
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        Interval::hull(&self.crossings(ray))
+    }
+    fn normal(&self, point: Vector) -> Vector {
+        let frame = self.frame();
+        let p = frame.point_to_local(&point);
+        let rho = p.xy().norm();
+        // Whichever surface the point is closest to
+        let side = (rho - self.radius).abs();
+        let bottom = p.z.abs();
+        let top = (p.z - self.height).abs();
+        let normal = if side < bottom.min(top) && rho > 0.0 {
+            Vector::new(p.x / rho, p.y / rho, 0.0)
+        } else if bottom < top {
+            Vector::new(0.0, 0.0, -1.0)
+        } else {
+            Vector::new(0.0, 0.0, 1.0)
+        };
+        frame.normal(&normal)
+    }
+    fn bounds(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(self.frame().bounds(&Aabb::new(
+            Vector::new(-r, -r, 0.0),
+            Vector::new(r, r, self.height),
+        )))
+    }
+    fn material(&self) -> &str {
+        &self.material
+    }
+    fn set_material(&mut self, material: String) {
+        self.material = material;
+    }
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+    fn into_shape(self) -> Shape {
+        Shape::Cylinder(self)
+    }
+    fn pos(&self) -> &Vector {
+        &self.origin
+    }
+    fn set_pos(&mut self, pos: Vector) {
+        self.origin = pos;
+    }
+}
+impl Display for Cylinder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Cylinder of radius {r} from ({ox}, {oy}, {oz}) to ({ex}, {ey}, {ez})",
+            r = self.radius,
+            ox = self.origin[0],
+            oy = self.origin[1],
+            oz = self.origin[2],
+            ex = self.origin[0] + self.axis[0] * self.height,
+            ey = self.origin[1] + self.axis[1] * self.height,
+            ez = self.origin[2] + self.axis[2] * self.height,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::test_util::{assert_bounds, assert_hit, assert_intervals, assert_miss, ray};
+
+    /// Radius 1, from y = 0 to y = 2
+    fn cylinder() -> Cylinder {
+        Cylinder::new(Vector::zeros(), Vector::new(0.0, 1.0, 0.0), 1.0, 2.0)
+    }
+
+    #[test]
+    fn hits_the_side() {
+        let ray = ray([-5.0, 1.0, 0.0], [1.0, 0.0, 0.0]);
+        assert_hit(&cylinder(), &ray, 4.0, Vector::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn hits_the_caps() {
+        let down = ray([0.5, 5.0, 0.0], [0.0, -1.0, 0.0]);
+        assert_hit(&cylinder(), &down, 3.0, Vector::new(0.0, 1.0, 0.0));
+        let up = ray([0.5, -5.0, 0.0], [0.0, 1.0, 0.0]);
+        assert_hit(&cylinder(), &up, 5.0, Vector::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn leaves_from_inside() {
+        let ray = ray([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]);
+        assert_hit(&cylinder(), &ray, 1.0, Vector::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn misses_above_and_beside() {
+        assert_miss(&cylinder(), &ray([-5.0, 3.0, 0.0], [1.0, 0.0, 0.0]));
+        assert_miss(&cylinder(), &ray([2.0, 5.0, 0.0], [0.0, -1.0, 0.0]));
+    }
+
+    #[test]
+    fn inside_between_the_side_or_the_caps() {
+        let across = ray([-5.0, 1.0, 0.0], [1.0, 0.0, 0.0]);
+        assert_intervals(&cylinder().intervals(&across), &[(4.0, 6.0)]);
+        let along = ray([0.5, 5.0, 0.0], [0.0, -1.0, 0.0]);
+        assert_intervals(&cylinder().intervals(&along), &[(3.0, 5.0)]);
+    }
+
+    #[test]
+    fn bounds_follow_the_axis() {
+        assert_bounds(&cylinder(), [-1.0, 0.0, -1.0], [1.0, 2.0, 1.0]);
+    }
+}
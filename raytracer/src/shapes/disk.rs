@@ -0,0 +1,177 @@
+use std::fmt::Display;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accel::Aabb,
+    material::DEFAULT_MATERIAL,
+    sampling::{concentric_disk, orthonormal_basis},
+    scene::Ray,
+    shapes::{Intersection, Object, Shape, Transform, Vector},
+};
+
+/// Flat circle, like a [`Plane`](crate::shapes::Plane) cut to a radius
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Disk {
+    /// Center of the disk
+    pub origin: Vector,
+    pub normal: Vector,
+    pub radius: f64,
+    /// Name of the [`Material`](crate::material::Material) used to shade this shape
+    #[cfg_attr(feature = "serde", serde(default = "crate::shapes::default_material"))]
+    pub material: String,
+    /// Applied on top of the other fields
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Transform::is_identity")
+    )]
+    pub transform: Transform,
+}
+impl Disk {
+    pub fn new(origin: Vector, normal: Vector, radius: f64) -> Self {
+        debug_assert!(radius > 0.0);
+        Disk {
+            origin,
+            normal: normal.normalize(),
+            radius,
+            material: DEFAULT_MATERIAL.to_string(),
+            transform: Transform::IDENTITY,
+        }
+    }
+    pub fn new_with_material(origin: Vector, normal: Vector, radius: f64, material: &str) -> Self {
+        let this = Disk::new(origin, normal, radius);
+        Disk {
+            material: material.to_string(),
+            ..this
+        }
+    }
+}
+impl Object for Disk {
+    fn distance(&self, ray: &Ray) -> Intersection {
+        let ray = self.transform.ray_to_local(ray);
+        let denom = self.normal.dot(&ray.direction);
+        if float!(denom -> 0) {
+            return Intersection::Miss;
+        }
+        let t = (self.origin - ray.origin).dot(&self.normal) / denom;
+        if (ray.at(t) - self.origin).norm_squared() > self.radius.powi(2) {
+            return Intersection::Miss;
+        }
+        Intersection::nearest([t])
+    }
+    fn normal(&self, _point: Vector) -> Vector {
+        self.transform.normal(&self.normal)
+    }
+    fn bounds(&self) -> Option<Aabb> {
+        // How far the rim reaches along each axis
+        let reach = Vector::from_fn(|axis, _| {
+            (1.0 - self.normal[axis].powi(2)).max(0.0).sqrt() * self.radius
+        });
+        Some(
+            self.transform
+                .bounds(&Aabb::new(self.origin - reach, self.origin + reach)),
+        )
+    }
+    fn area(&self) -> Option<f64> {
+        let scale = self.transform.uniform_scale()?;
+        Some(std::f64::consts::PI * (self.radius * scale).powi(2))
+    }
+    fn sample_surface(&self, u: (f64, f64)) -> Option<(Vector, Vector)> {
+        self.transform.uniform_scale()?;
+        let (x, y) = concentric_disk(u);
+        let (tangent, bitangent) = orthonormal_basis(&self.normal);
+        let point = self.origin + (tangent * x + bitangent * y) * self.radius;
+        Some((
+            self.transform.point(&point),
+            self.transform.normal(&self.normal),
+        ))
+    }
+    fn material(&self) -> &str {
+        &self.material
+    }
+    fn set_material(&mut self, material: String) {
+        self.material = material;
+    }
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+    fn into_shape(self) -> Shape {
+        Shape::Disk(self)
+    }
+    fn pos(&self) -> &Vector {
+        &self.origin
+    }
+    fn set_pos(&mut self, pos: Vector) {
+        self.origin = pos;
+    }
+}
+impl Display for Disk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "○(({ox}, {oy}, {oz}), ({nx}, {ny}, {nz}), {r})",
+            ox = self.origin[0],
+            oy = self.origin[1],
+            oz = self.origin[2],
+            nx = self.normal[0],
+            ny = self.normal[1],
+            nz = self.normal[2],
+            r = self.radius,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::test_util::{assert_bounds, assert_hit, assert_intervals, assert_miss, ray};
+
+    fn disk() -> Disk {
+        Disk::new(Vector::new(0.0, 0.0, 2.0), Vector::new(0.0, 0.0, -1.0), 1.0)
+    }
+
+    #[test]
+    fn hits_inside_the_radius() {
+        let ray = ray([0.5, 0.5, 0.0], [0.0, 0.0, 1.0]);
+        assert_hit(&disk(), &ray, 2.0, Vector::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn misses_outside_the_radius() {
+        assert_miss(&disk(), &ray([0.8, 0.8, 0.0], [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn misses_parallel_and_behind() {
+        assert_miss(&disk(), &ray([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]));
+        assert_miss(&disk(), &ray([0.0, 0.0, 3.0], [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn samples_points_on_the_disk() {
+        let disk = disk();
+        let (point, normal) = disk.sample_surface((0.9, 0.2)).unwrap();
+        assert!((point.z - 2.0).abs() < 1e-9);
+        assert!(point.xy().norm() <= 1.0 + 1e-9);
+        assert!((normal - Vector::new(0.0, 0.0, -1.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn has_no_inside() {
+        let ray = ray([0.5, 0.5, 0.0], [0.0, 0.0, 1.0]);
+        assert_intervals(&disk().intervals(&ray), &[]);
+    }
+
+    #[test]
+    fn bounds_are_flat_along_the_normal() {
+        assert_bounds(&disk(), [-1.0, -1.0, 2.0], [1.0, 1.0, 2.0]);
+        let tilted = Disk::new(Vector::zeros(), Vector::new(1.0, 0.0, 1.0), 1.0);
+        let reach = 0.5f64.sqrt();
+        assert_bounds(&tilted, [-reach, -1.0, -reach], [reach, 1.0, reach]);
+    }
+}
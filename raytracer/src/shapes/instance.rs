@@ -0,0 +1,139 @@
+use std::{fmt::Display, sync::Arc};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accel::Aabb,
+    material::DEFAULT_MATERIAL,
+    scene::Ray,
+    shapes::{Hit, Intersection, Interval, Object, Shape, Transform, Vector},
+};
+
+/// A shape from the scene's geometry library placed with its own transform,
+/// so that the same geometry can appear many times while being stored once.
+///
+/// Scene files only store the geometry's name, call
+/// [`Scene::load`](crate::scene::Scene::load) to resolve it.
+/// Until then the instance is invisible.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Instance {
+    /// Name of the shape in [`Scene::geometry`](crate::scene::Scene::geometry)
+    pub geometry: String,
+    /// Material used instead of the geometry's own
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub material: Option<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Transform::is_identity")
+    )]
+    pub transform: Transform,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    shape: Option<Arc<Shape>>,
+}
+impl Instance {
+    /// Places the library geometry called `geometry`, which must still be resolved
+    pub fn new(geometry: impl Into<String>, transform: Transform) -> Self {
+        Instance {
+            geometry: geometry.into(),
+            material: None,
+            transform,
+            shape: None,
+        }
+    }
+    /// The geometry being placed, `None` if it hasn't been resolved yet
+    pub fn shape(&self) -> Option<&Arc<Shape>> {
+        self.shape.as_ref()
+    }
+    pub fn set_shape(&mut self, shape: Arc<Shape>) {
+        self.shape = Some(shape);
+    }
+}
+impl Object for Instance {
+    fn distance(&self, ray: &Ray) -> Intersection {
+        match &self.shape {
+            Some(shape) => shape.distance(&self.transform.ray_to_local(ray)),
+            None => Intersection::Miss,
+        }
+    }
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        match &self.shape {
+            Some(shape) => shape.intervals(&self.transform.ray_to_local(ray)),
+            None => Vec::new(),
+        }
+    }
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        self.shape.as_ref()?.hit(&self.transform.ray_to_local(ray))
+    }
+    fn hit_normal(&self, ray: &Ray, hit: &Hit) -> Vector {
+        match &self.shape {
+            Some(shape) => self
+                .transform
+                .normal(&shape.hit_normal(&self.transform.ray_to_local(ray), hit)),
+            None => Vector::zeros(),
+        }
+    }
+    fn normal(&self, point: Vector) -> Vector {
+        match &self.shape {
+            Some(shape) => self
+                .transform
+                .normal(&shape.normal(self.transform.point_to_local(&point))),
+            None => Vector::zeros(),
+        }
+    }
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.shape.as_ref()?.bounds()?;
+        Some(self.transform.bounds(&bounds))
+    }
+    fn area(&self) -> Option<f64> {
+        let scale = self.transform.uniform_scale()?;
+        Some(self.shape.as_ref()?.area()? * scale.powi(2))
+    }
+    fn sample_surface(&self, u: (f64, f64)) -> Option<(Vector, Vector)> {
+        self.transform.uniform_scale()?;
+        let (point, normal) = self.shape.as_ref()?.sample_surface(u)?;
+        Some((self.transform.point(&point), self.transform.normal(&normal)))
+    }
+    fn material(&self) -> &str {
+        match (&self.material, &self.shape) {
+            (Some(material), _) => material,
+            (None, Some(shape)) => shape.material(),
+            (None, None) => DEFAULT_MATERIAL,
+        }
+    }
+    fn set_material(&mut self, material: String) {
+        self.material = Some(material);
+    }
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+    fn into_shape(self) -> Shape {
+        Shape::Instance(self)
+    }
+    fn pos(&self) -> &Vector {
+        self.transform.offset()
+    }
+    fn set_pos(&mut self, pos: Vector) {
+        self.transform.set_offset(pos);
+    }
+}
+impl Display for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pos = self.transform.offset();
+        write!(
+            f,
+            "Instance of {geometry} at ({ox}, {oy}, {oz})",
+            geometry = self.geometry,
+            ox = pos[0],
+            oy = pos[1],
+            oz = pos[2],
+        )
+    }
+}
@@ -0,0 +1,375 @@
+use std::{collections::HashMap, fmt::Display, sync::Arc};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accel::{Aabb, Bvh},
+    material::DEFAULT_MATERIAL,
+    scene::Ray,
+    shapes::{
+        triangle::{line_triangle, moller_trumbore},
+        Hit, Intersection, Interval, Object, Shape, Transform, Vector,
+    },
+    MIN_T,
+};
+
+/// Triangles sharing a list of vertices.
+///
+/// Cloning a mesh is cheap, clones share the same vertex and index buffers.
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "MeshDef", into = "MeshDef")
+)]
+pub struct Mesh {
+    /// Offset applied to every vertex
+    pub origin: Vector,
+    data: Arc<MeshData>,
+    /// Name of the [`Material`](crate::material::Material) used to shade this shape
+    pub material: String,
+    /// Applied after the offset
+    pub transform: Transform,
+}
+
+struct MeshData {
+    vertices: Vec<Vector>,
+    /// One per vertex for smooth shading, or empty to use each face's normal
+    normals: Vec<Vector>,
+    /// Indices into `vertices` for each triangle
+    indices: Vec<[u32; 3]>,
+    /// Hierarchy over the triangles, using the same numbering as `indices`
+    bvh: Bvh,
+}
+
+#[derive(Debug)]
+pub enum MeshError {
+    /// A triangle references a vertex that doesn't exist
+    IndexOutOfBounds { index: u32, vertices: usize },
+    /// Normals were given but not one per vertex
+    NormalCount { normals: usize, vertices: usize },
+}
+impl Display for MeshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MeshError::IndexOutOfBounds { index, vertices } => {
+                write!(f, "index {index} is out of bounds for {vertices} vertices")
+            }
+            MeshError::NormalCount { normals, vertices } => {
+                write!(f, "got {normals} normals for {vertices} vertices")
+            }
+        }
+    }
+}
+impl std::error::Error for MeshError {}
+
+impl Mesh {
+    /// Creates a mesh from its triangles' vertex indices.
+    /// `normals` may be empty, otherwise there must be one per vertex.
+    pub fn new(
+        vertices: Vec<Vector>,
+        normals: Vec<Vector>,
+        indices: Vec<[u32; 3]>,
+    ) -> Result<Self, MeshError> {
+        if !normals.is_empty() && normals.len() != vertices.len() {
+            return Err(MeshError::NormalCount {
+                normals: normals.len(),
+                vertices: vertices.len(),
+            });
+        }
+        if let Some(&index) = indices
+            .iter()
+            .flatten()
+            .find(|&&i| i as usize >= vertices.len())
+        {
+            return Err(MeshError::IndexOutOfBounds {
+                index,
+                vertices: vertices.len(),
+            });
+        }
+        let bounds: Vec<Aabb> = indices
+            .iter()
+            .map(|tri| Aabb::from_points(tri.map(|i| &vertices[i as usize])).pad(MIN_T))
+            .collect();
+        let normals = normals.into_iter().map(|n| n.normalize()).collect();
+        Ok(Mesh {
+            origin: Vector::zeros(),
+            data: Arc::new(MeshData {
+                vertices,
+                normals,
+                indices,
+                bvh: Bvh::build(&bounds),
+            }),
+            material: DEFAULT_MATERIAL.to_string(),
+            transform: Transform::IDENTITY,
+        })
+    }
+    pub fn vertices(&self) -> &[Vector] {
+        &self.data.vertices
+    }
+    pub fn normals(&self) -> &[Vector] {
+        &self.data.normals
+    }
+    pub fn indices(&self) -> &[[u32; 3]] {
+        &self.data.indices
+    }
+    /// Whether every edge is shared by exactly two triangles, so that the mesh has an inside.
+    /// Vertices are compared by position, faces split apart for sharp edges still count as joined.
+    pub fn is_closed(&self) -> bool {
+        // Adding zero turns -0 into 0 so both get the same bits
+        let position = |v: u32| self.data.vertices[v as usize].map(|x| (x + 0.0).to_bits());
+        let mut edges: HashMap<_, usize> = HashMap::new();
+        for &[a, b, c] in &self.data.indices {
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                let (from, to) = (position(from), position(to));
+                let edge = if from.as_slice() < to.as_slice() {
+                    (from, to)
+                } else {
+                    (to, from)
+                };
+                *edges.entry(edge).or_default() += 1;
+            }
+        }
+        !edges.is_empty() && edges.values().all(|&count| count == 2)
+    }
+    fn triangle(&self, i: usize) -> [&Vector; 3] {
+        self.data.indices[i].map(|v| &self.data.vertices[v as usize])
+    }
+    /// Normal of triangle `i` at barycentric coordinates `(u, v)`, before the transform
+    fn triangle_normal(&self, i: usize, (u, v): (f64, f64)) -> Vector {
+        if self.data.normals.is_empty() {
+            let [a, b, c] = self.triangle(i);
+            (b - a).cross(&(c - a))
+        } else {
+            let [na, nb, nc] = self.data.indices[i].map(|v| self.data.normals[v as usize]);
+            na * (1.0 - u - v) + nb * u + nc * v
+        }
+    }
+}
+impl Object for Mesh {
+    fn distance(&self, ray: &Ray) -> Intersection {
+        match self.hit(ray) {
+            Some(hit) => Intersection::Hit(hit.t),
+            None => Intersection::Miss,
+        }
+    }
+    /// Remembers the triangle that was hit and where on it
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        let ray = &self.transform.ray_to_local(ray) - self.origin;
+        self.data
+            .bvh
+            .closest(&ray, f64::INFINITY, |i| {
+                let [a, b, c] = self.triangle(i);
+                moller_trumbore(&ray, a, b, c).map(|(t, u, v)| Hit {
+                    t,
+                    part: i,
+                    uv: (u, v),
+                })
+            })
+            .map(|(hit, _)| hit)
+    }
+    fn hit_normal(&self, _ray: &Ray, hit: &Hit) -> Vector {
+        self.transform
+            .normal(&self.triangle_normal(hit.part, hit.uv))
+    }
+    /// Treats the mesh as closed, the ray is inside between every other triangle it crosses
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let ray = &self.transform.ray_to_local(ray) - self.origin;
+        let mut crossings = Vec::new();
+        self.data.bvh.query_line(&ray, |i| {
+            let [a, b, c] = self.triangle(i);
+            crossings.extend(line_triangle(&ray, a, b, c).map(|(t, _, _)| t));
+        });
+        Interval::alternating(crossings)
+    }
+    fn normal(&self, point: Vector) -> Vector {
+        /*
+          Without a `Hit` we only get the point, so look for the triangle it lies on:
+          among the triangles whose box contains it, pick the one whose plane
+          is closest and that contains its projection
+        */
+        let point = self.transform.point_to_local(&point) - self.origin;
+        let mut best: Option<(f64, usize, f64, f64)> = None;
+        self.data.bvh.query_point(&point, |i| {
+            let [a, b, c] = self.triangle(i);
+            let (e1, e2) = (b - a, c - a);
+            let face = e1.cross(&e2).normalize();
+            let plane_distance = face.dot(&(point - a)).abs();
+            // Barycentric coordinates of the point projected on the triangle's plane
+            let p = point - a;
+            let (d11, d12, d22) = (e1.dot(&e1), e1.dot(&e2), e2.dot(&e2));
+            let (dp1, dp2) = (p.dot(&e1), p.dot(&e2));
+            let denom = d11 * d22 - d12 * d12;
+            let u = (d22 * dp1 - d12 * dp2) / denom;
+            let v = (d11 * dp2 - d12 * dp1) / denom;
+            let outside = (-u).max(-v).max(u + v - 1.0).max(0.0);
+            let score = plane_distance + outside;
+            if best.is_none_or(|(best_score, ..)| score < best_score) {
+                best = Some((score, i, u, v));
+            }
+        });
+        let (i, u, v) = match best {
+            Some((_, i, u, v)) => (i, u.clamp(0.0, 1.0), v.clamp(0.0, 1.0)),
+            // Shouldn't happen for points obtained from `distance`
+            None => return Vector::zeros(),
+        };
+        self.transform.normal(&self.triangle_normal(i, (u, v)))
+    }
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.data.bvh.bounds();
+        Some(self.transform.bounds(&Aabb {
+            min: bounds.min + self.origin,
+            max: bounds.max + self.origin,
+        }))
+    }
+    fn material(&self) -> &str {
+        &self.material
+    }
+    fn set_material(&mut self, material: String) {
+        self.material = material;
+    }
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+    fn into_shape(self) -> Shape {
+        Shape::Mesh(self)
+    }
+    fn pos(&self) -> &Vector {
+        &self.origin
+    }
+    fn set_pos(&mut self, pos: Vector) {
+        self.origin = pos;
+    }
+}
+impl Display for Mesh {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Mesh of {n} triangles at ({ox}, {oy}, {oz})",
+            n = self.data.indices.len(),
+            ox = self.origin[0],
+            oy = self.origin[1],
+            oz = self.origin[2],
+        )
+    }
+}
+
+/// What gets stored in a scene file, the hierarchy is rebuilt when loading
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct MeshDef {
+    #[serde(default)]
+    origin: Vector,
+    vertices: Vec<Vector>,
+    #[serde(default)]
+    normals: Vec<Vector>,
+    indices: Vec<[u32; 3]>,
+    #[serde(default = "crate::shapes::default_material")]
+    material: String,
+    #[serde(default, skip_serializing_if = "Transform::is_identity")]
+    transform: Transform,
+}
+#[cfg(feature = "serde")]
+impl TryFrom<MeshDef> for Mesh {
+    type Error = MeshError;
+    fn try_from(def: MeshDef) -> Result<Self, Self::Error> {
+        let mesh = Mesh::new(def.vertices, def.normals, def.indices)?;
+        Ok(Mesh {
+            origin: def.origin,
+            material: def.material,
+            transform: def.transform,
+            ..mesh
+        })
+    }
+}
+#[cfg(feature = "serde")]
+impl From<Mesh> for MeshDef {
+    fn from(mesh: Mesh) -> Self {
+        MeshDef {
+            origin: mesh.origin,
+            vertices: mesh.data.vertices.clone(),
+            normals: mesh.data.normals.clone(),
+            indices: mesh.data.indices.clone(),
+            material: mesh.material,
+            transform: mesh.transform,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit square on the XY plane, normals leaning away from its middle along X
+    fn square() -> Mesh {
+        let vertices = vec![
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        ];
+        let normals = vec![
+            Vector::new(-1.0, 0.0, 1.0),
+            Vector::new(1.0, 0.0, 1.0),
+            Vector::new(1.0, 0.0, 1.0),
+            Vector::new(-1.0, 0.0, 1.0),
+        ];
+        Mesh::new(vertices, normals, vec![[0, 1, 2], [0, 2, 3]]).unwrap()
+    }
+    fn down_at(x: f64, y: f64) -> Ray {
+        Ray {
+            origin: Vector::new(x, y, 1.0),
+            direction: Vector::new(0.0, 0.0, -1.0),
+        }
+    }
+
+    #[test]
+    fn only_meshes_without_borders_are_closed() {
+        assert!(!square().is_closed());
+        let vertices = vec![
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+        ];
+        let faces = vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]];
+        let tetrahedron = Mesh::new(vertices.clone(), Vec::new(), faces).unwrap();
+        assert!(tetrahedron.is_closed());
+        // Each face with its own copy of the vertices, like for flat shading
+        let split: Vec<_> = [[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]]
+            .iter()
+            .flat_map(|face: &[usize; 3]| face.map(|v| vertices[v]))
+            .collect();
+        let faces = (0..4).map(|f| [3 * f, 3 * f + 1, 3 * f + 2]).collect();
+        assert!(Mesh::new(split, Vec::new(), faces).unwrap().is_closed());
+    }
+
+    #[test]
+    fn remembers_the_triangle_it_hit() {
+        let mesh = square();
+        let ray = down_at(0.75, 0.5);
+        let hit = mesh.hit(&ray).unwrap();
+        assert_eq!(hit.part, 0);
+        assert!((hit.t - 1.0).abs() < 1e-9);
+        // A quarter of the way from the first vertex to the second, half way to the third
+        let expected = Vector::new(0.5, 0.0, 1.0).normalize();
+        assert!((mesh.hit_normal(&ray, &hit) - expected).norm() < 1e-9);
+    }
+
+    #[test]
+    fn interpolates_the_same_on_a_shared_edge() {
+        let mut mesh = square();
+        mesh.transform = Transform::translation(Vector::new(5.0, 0.0, 0.0));
+        let ray = down_at(5.5, 0.5);
+        let hit = mesh.hit(&ray).unwrap();
+        let normal = mesh.hit_normal(&ray, &hit);
+        assert!(
+            (normal - Vector::new(0.0, 0.0, 1.0)).norm() < 1e-9,
+            "{normal}"
+        );
+    }
+}
@@ -1,15 +1,32 @@
 use std::fmt::Display;
 
-use crate::{
-    color::{Color, WHITE},
-    scene::Ray,
-};
+use crate::{accel::Aabb, material::DEFAULT_MATERIAL, scene::Ray, MIN_T};
 
+mod cone;
+mod csg;
+mod cuboid;
+mod cylinder;
+mod disk;
+mod instance;
+mod mesh;
 mod plane;
 mod sphere;
+mod torus;
+mod transform;
+mod triangle;
 
+pub use cone::Cone;
+pub use csg::{Csg, CsgOperation};
+pub use cuboid::Cuboid;
+pub use cylinder::Cylinder;
+pub use disk::Disk;
+pub use instance::Instance;
+pub use mesh::{Mesh, MeshError};
 pub use plane::Plane;
 pub use sphere::Sphere;
+pub use torus::Torus;
+pub use transform::{Transform, TransformError};
+pub use triangle::Triangle;
 
 pub type Vector = na::Vector3<f64>;
 
@@ -26,10 +43,20 @@ pub const YN: Vector = na::Vector3::new(0.0, -1.0, 0.0);
     derive(serde::Serialize, serde::Deserialize),
     serde(tag = "type")
 )]
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub enum Shape {
     Sphere(Sphere),
     Plane(Plane),
+    Triangle(Triangle),
+    Mesh(Mesh),
+    Instance(Instance),
+    #[cfg_attr(feature = "serde", serde(alias = "Box"))]
+    Cuboid(Cuboid),
+    Cylinder(Cylinder),
+    Cone(Cone),
+    Disk(Disk),
+    Torus(Torus),
+    Csg(Csg),
 }
 impl Shape {
     pub fn new_sphere(origin: [f64; 3], radius: f64) -> Self {
@@ -44,24 +71,124 @@ impl Shape {
             na::Vector3::new(nx, ny, nz),
         ))
     }
+    pub fn new_triangle(a: [f64; 3], b: [f64; 3], c: [f64; 3]) -> Self {
+        Shape::Triangle(Triangle::new(a.into(), b.into(), c.into()))
+    }
+    pub fn new_cuboid(a: [f64; 3], b: [f64; 3]) -> Self {
+        Shape::Cuboid(Cuboid::new(a.into(), b.into()))
+    }
+    pub fn new_cylinder(origin: [f64; 3], axis: [f64; 3], radius: f64, height: f64) -> Self {
+        Shape::Cylinder(Cylinder::new(origin.into(), axis.into(), radius, height))
+    }
+    pub fn new_cone(origin: [f64; 3], axis: [f64; 3], radius: f64, height: f64) -> Self {
+        Shape::Cone(Cone::new(origin.into(), axis.into(), radius, height))
+    }
+    pub fn new_disk(origin: [f64; 3], normal: [f64; 3], radius: f64) -> Self {
+        Shape::Disk(Disk::new(origin.into(), normal.into(), radius))
+    }
+    pub fn new_torus(
+        origin: [f64; 3],
+        axis: [f64; 3],
+        major_radius: f64,
+        minor_radius: f64,
+    ) -> Self {
+        Shape::Torus(Torus::new(
+            origin.into(),
+            axis.into(),
+            major_radius,
+            minor_radius,
+        ))
+    }
 }
 impl Object for Shape {
     fn distance(&self, ray: &Ray) -> Intersection {
         match self {
             Shape::Sphere(sphere) => sphere.distance(ray),
             Shape::Plane(plane) => plane.distance(ray),
+            Shape::Triangle(triangle) => triangle.distance(ray),
+            Shape::Mesh(mesh) => mesh.distance(ray),
+            Shape::Instance(instance) => instance.distance(ray),
+            Shape::Cuboid(cuboid) => cuboid.distance(ray),
+            Shape::Cylinder(cylinder) => cylinder.distance(ray),
+            Shape::Cone(cone) => cone.distance(ray),
+            Shape::Disk(disk) => disk.distance(ray),
+            Shape::Torus(torus) => torus.distance(ray),
+            Shape::Csg(csg) => csg.distance(ray),
+        }
+    }
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        match self {
+            Shape::Sphere(sphere) => sphere.hit(ray),
+            Shape::Plane(plane) => plane.hit(ray),
+            Shape::Triangle(triangle) => triangle.hit(ray),
+            Shape::Mesh(mesh) => mesh.hit(ray),
+            Shape::Instance(instance) => instance.hit(ray),
+            Shape::Cuboid(cuboid) => cuboid.hit(ray),
+            Shape::Cylinder(cylinder) => cylinder.hit(ray),
+            Shape::Cone(cone) => cone.hit(ray),
+            Shape::Disk(disk) => disk.hit(ray),
+            Shape::Torus(torus) => torus.hit(ray),
+            Shape::Csg(csg) => csg.hit(ray),
         }
     }
-    fn color(&self) -> Color {
+    fn material(&self) -> &str {
         match self {
-            Shape::Sphere(sphere) => sphere.color(),
-            Shape::Plane(plane) => plane.color(),
+            Shape::Sphere(sphere) => sphere.material(),
+            Shape::Plane(plane) => plane.material(),
+            Shape::Triangle(triangle) => triangle.material(),
+            Shape::Mesh(mesh) => mesh.material(),
+            Shape::Instance(instance) => instance.material(),
+            Shape::Cuboid(cuboid) => cuboid.material(),
+            Shape::Cylinder(cylinder) => cylinder.material(),
+            Shape::Cone(cone) => cone.material(),
+            Shape::Disk(disk) => disk.material(),
+            Shape::Torus(torus) => torus.material(),
+            Shape::Csg(csg) => csg.material(),
         }
     }
-    fn set_color(&mut self, color: Color) {
+    fn set_material(&mut self, material: String) {
         match self {
-            Shape::Sphere(sphere) => sphere.set_color(color),
-            Shape::Plane(plane) => plane.set_color(color),
+            Shape::Sphere(sphere) => sphere.set_material(material),
+            Shape::Plane(plane) => plane.set_material(material),
+            Shape::Triangle(triangle) => triangle.set_material(material),
+            Shape::Mesh(mesh) => mesh.set_material(material),
+            Shape::Instance(instance) => instance.set_material(material),
+            Shape::Cuboid(cuboid) => cuboid.set_material(material),
+            Shape::Cylinder(cylinder) => cylinder.set_material(material),
+            Shape::Cone(cone) => cone.set_material(material),
+            Shape::Disk(disk) => disk.set_material(material),
+            Shape::Torus(torus) => torus.set_material(material),
+            Shape::Csg(csg) => csg.set_material(material),
+        }
+    }
+    fn transform(&self) -> &Transform {
+        match self {
+            Shape::Sphere(sphere) => sphere.transform(),
+            Shape::Plane(plane) => plane.transform(),
+            Shape::Triangle(triangle) => triangle.transform(),
+            Shape::Mesh(mesh) => mesh.transform(),
+            Shape::Instance(instance) => instance.transform(),
+            Shape::Cuboid(cuboid) => cuboid.transform(),
+            Shape::Cylinder(cylinder) => cylinder.transform(),
+            Shape::Cone(cone) => cone.transform(),
+            Shape::Disk(disk) => disk.transform(),
+            Shape::Torus(torus) => torus.transform(),
+            Shape::Csg(csg) => csg.transform(),
+        }
+    }
+    fn set_transform(&mut self, transform: Transform) {
+        match self {
+            Shape::Sphere(sphere) => sphere.set_transform(transform),
+            Shape::Plane(plane) => plane.set_transform(transform),
+            Shape::Triangle(triangle) => triangle.set_transform(transform),
+            Shape::Mesh(mesh) => mesh.set_transform(transform),
+            Shape::Instance(instance) => instance.set_transform(transform),
+            Shape::Cuboid(cuboid) => cuboid.set_transform(transform),
+            Shape::Cylinder(cylinder) => cylinder.set_transform(transform),
+            Shape::Cone(cone) => cone.set_transform(transform),
+            Shape::Disk(disk) => disk.set_transform(transform),
+            Shape::Torus(torus) => torus.set_transform(transform),
+            Shape::Csg(csg) => csg.set_transform(transform),
         }
     }
 
@@ -72,12 +199,30 @@ impl Object for Shape {
         match self {
             Shape::Sphere(sphere) => sphere.pos(),
             Shape::Plane(plane) => plane.pos(),
+            Shape::Triangle(triangle) => triangle.pos(),
+            Shape::Mesh(mesh) => mesh.pos(),
+            Shape::Instance(instance) => instance.pos(),
+            Shape::Cuboid(cuboid) => cuboid.pos(),
+            Shape::Cylinder(cylinder) => cylinder.pos(),
+            Shape::Cone(cone) => cone.pos(),
+            Shape::Disk(disk) => disk.pos(),
+            Shape::Torus(torus) => torus.pos(),
+            Shape::Csg(csg) => csg.pos(),
         }
     }
     fn set_pos(&mut self, pos: Vector) {
         match self {
             Shape::Sphere(sphere) => sphere.set_pos(pos),
             Shape::Plane(plane) => plane.set_pos(pos),
+            Shape::Triangle(triangle) => triangle.set_pos(pos),
+            Shape::Mesh(mesh) => mesh.set_pos(pos),
+            Shape::Instance(instance) => instance.set_pos(pos),
+            Shape::Cuboid(cuboid) => cuboid.set_pos(pos),
+            Shape::Cylinder(cylinder) => cylinder.set_pos(pos),
+            Shape::Cone(cone) => cone.set_pos(pos),
+            Shape::Disk(disk) => disk.set_pos(pos),
+            Shape::Torus(torus) => torus.set_pos(pos),
+            Shape::Csg(csg) => csg.set_pos(pos),
         }
     }
 
@@ -85,6 +230,168 @@ impl Object for Shape {
         match self {
             Shape::Sphere(sphere) => sphere.normal(point),
             Shape::Plane(plane) => plane.normal(point),
+            Shape::Triangle(triangle) => triangle.normal(point),
+            Shape::Mesh(mesh) => mesh.normal(point),
+            Shape::Instance(instance) => instance.normal(point),
+            Shape::Cuboid(cuboid) => cuboid.normal(point),
+            Shape::Cylinder(cylinder) => cylinder.normal(point),
+            Shape::Cone(cone) => cone.normal(point),
+            Shape::Disk(disk) => disk.normal(point),
+            Shape::Torus(torus) => torus.normal(point),
+            Shape::Csg(csg) => csg.normal(point),
+        }
+    }
+    fn hit_normal(&self, ray: &Ray, hit: &Hit) -> Vector {
+        match self {
+            Shape::Sphere(sphere) => sphere.hit_normal(ray, hit),
+            Shape::Plane(plane) => plane.hit_normal(ray, hit),
+            Shape::Triangle(triangle) => triangle.hit_normal(ray, hit),
+            Shape::Mesh(mesh) => mesh.hit_normal(ray, hit),
+            Shape::Instance(instance) => instance.hit_normal(ray, hit),
+            Shape::Cuboid(cuboid) => cuboid.hit_normal(ray, hit),
+            Shape::Cylinder(cylinder) => cylinder.hit_normal(ray, hit),
+            Shape::Cone(cone) => cone.hit_normal(ray, hit),
+            Shape::Disk(disk) => disk.hit_normal(ray, hit),
+            Shape::Torus(torus) => torus.hit_normal(ray, hit),
+            Shape::Csg(csg) => csg.hit_normal(ray, hit),
+        }
+    }
+    fn bounds(&self) -> Option<Aabb> {
+        match self {
+            Shape::Sphere(sphere) => sphere.bounds(),
+            Shape::Plane(plane) => plane.bounds(),
+            Shape::Triangle(triangle) => triangle.bounds(),
+            Shape::Mesh(mesh) => mesh.bounds(),
+            Shape::Instance(instance) => instance.bounds(),
+            Shape::Cuboid(cuboid) => cuboid.bounds(),
+            Shape::Cylinder(cylinder) => cylinder.bounds(),
+            Shape::Cone(cone) => cone.bounds(),
+            Shape::Disk(disk) => disk.bounds(),
+            Shape::Torus(torus) => torus.bounds(),
+            Shape::Csg(csg) => csg.bounds(),
+        }
+    }
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        match self {
+            Shape::Sphere(sphere) => sphere.intervals(ray),
+            Shape::Plane(plane) => plane.intervals(ray),
+            Shape::Triangle(triangle) => triangle.intervals(ray),
+            Shape::Mesh(mesh) => mesh.intervals(ray),
+            Shape::Instance(instance) => instance.intervals(ray),
+            Shape::Cuboid(cuboid) => cuboid.intervals(ray),
+            Shape::Cylinder(cylinder) => cylinder.intervals(ray),
+            Shape::Cone(cone) => cone.intervals(ray),
+            Shape::Disk(disk) => disk.intervals(ray),
+            Shape::Torus(torus) => torus.intervals(ray),
+            Shape::Csg(csg) => csg.intervals(ray),
+        }
+    }
+    fn area(&self) -> Option<f64> {
+        match self {
+            Shape::Sphere(sphere) => sphere.area(),
+            Shape::Plane(plane) => plane.area(),
+            Shape::Triangle(triangle) => triangle.area(),
+            Shape::Mesh(mesh) => mesh.area(),
+            Shape::Instance(instance) => instance.area(),
+            Shape::Cuboid(cuboid) => cuboid.area(),
+            Shape::Cylinder(cylinder) => cylinder.area(),
+            Shape::Cone(cone) => cone.area(),
+            Shape::Disk(disk) => disk.area(),
+            Shape::Torus(torus) => torus.area(),
+            Shape::Csg(csg) => csg.area(),
+        }
+    }
+    fn sample_surface(&self, u: (f64, f64)) -> Option<(Vector, Vector)> {
+        match self {
+            Shape::Sphere(sphere) => sphere.sample_surface(u),
+            Shape::Plane(plane) => plane.sample_surface(u),
+            Shape::Triangle(triangle) => triangle.sample_surface(u),
+            Shape::Mesh(mesh) => mesh.sample_surface(u),
+            Shape::Instance(instance) => instance.sample_surface(u),
+            Shape::Cuboid(cuboid) => cuboid.sample_surface(u),
+            Shape::Cylinder(cylinder) => cylinder.sample_surface(u),
+            Shape::Cone(cone) => cone.sample_surface(u),
+            Shape::Disk(disk) => disk.sample_surface(u),
+            Shape::Torus(torus) => torus.sample_surface(u),
+            Shape::Csg(csg) => csg.sample_surface(u),
+        }
+    }
+}
+
+/// Stretch of a ray spent inside an object, from where it enters to where it leaves
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+    pub enter: f64,
+    pub exit: f64,
+}
+impl Interval {
+    /// Inside between every other crossing of the surface, for closed shapes
+    pub(crate) fn alternating(mut crossings: Vec<f64>) -> Vec<Interval> {
+        crossings.sort_by(f64::total_cmp);
+        // A ray through an edge shared by two faces crosses both at the same spot
+        crossings.dedup_by(|a, b| (*a - *b).abs() <= f64::EPSILON * a.abs().max(1.0));
+        crossings
+            .chunks_exact(2)
+            .map(|pair| Interval {
+                enter: pair[0],
+                exit: pair[1],
+            })
+            .collect()
+    }
+    /// Inside between the first and last crossing, for convex shapes
+    pub(crate) fn hull(crossings: &[f64]) -> Vec<Interval> {
+        let enter = crossings.iter().copied().fold(f64::INFINITY, f64::min);
+        let exit = crossings.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        if crossings.len() >= 2 && enter < exit {
+            vec![Interval { enter, exit }]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Real roots of `a t² + b t + c`, smallest first
+pub(crate) fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a.abs() < f64::EPSILON {
+        // Not actually quadratic
+        let t = -c / b;
+        return t.is_finite().then_some((t, t));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    // Avoids subtracting nearly equal numbers, see Numerical Recipes §5.6
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        return Some((0.0, 0.0));
+    }
+    let (t0, t1) = (q / a, c / q);
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn default_material() -> String {
+    DEFAULT_MATERIAL.to_string()
+}
+
+/// Where a ray hits an object, remembering what's needed to shade it afterwards
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    /// Distance along the ray
+    pub t: f64,
+    /// Which piece of the object was hit, like the triangle of a mesh
+    pub part: usize,
+    /// Where on that piece, like the barycentric coordinates on a triangle
+    pub uv: (f64, f64),
+}
+impl Hit {
+    /// Hit on an object made of a single piece
+    pub fn at(t: f64) -> Self {
+        Hit {
+            t,
+            part: 0,
+            uv: (0.0, 0.0),
         }
     }
 }
@@ -93,30 +400,118 @@ pub enum Intersection {
     Hit(f64),
     Miss,
 }
+impl Intersection {
+    /// Closest of the distances that is in front of the ray
+    pub(crate) fn nearest(distances: impl IntoIterator<Item = f64>) -> Self {
+        distances
+            .into_iter()
+            .filter(|&t| t > MIN_T && t.is_finite())
+            .min_by(f64::total_cmp)
+            .map_or(Intersection::Miss, Intersection::Hit)
+    }
+}
 
 pub trait Object {
     fn intersects(&self, ray: &Ray) -> bool {
         let t = self.distance(ray);
-        match t {
-            Intersection::Hit(t) if t >= 0.0 => true,
-            _ => false,
-        }
+        matches!(t, Intersection::Hit(t) if t >= 0.0)
     }
     fn distance(&self, ray: &Ray) -> Intersection;
+    /// Like [`Object::distance`], also remembering where the object was hit
+    /// for [`Object::hit_normal`]
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        match self.distance(ray) {
+            Intersection::Hit(t) => Some(Hit::at(t)),
+            Intersection::Miss => None,
+        }
+    }
     fn normal(&self, point: Vector) -> Vector;
-    fn color(&self) -> Color {
-        WHITE
+    /// Normal where `ray` hits the object, `hit` having been found by [`Object::hit`]
+    fn hit_normal(&self, ray: &Ray, hit: &Hit) -> Vector {
+        self.normal(ray.at(hit.t))
+    }
+    /// Box enclosing the whole object, `None` if it extends infinitely
+    fn bounds(&self) -> Option<Aabb>;
+    /// Every stretch of the infinite line the ray lies on, behind its origin included,
+    /// that is inside the object, sorted and not overlapping. Used to combine objects
+    /// with [`Csg`]. Flat objects have no inside and give none.
+    fn intervals(&self, _ray: &Ray) -> Vec<Interval> {
+        Vec::new()
+    }
+    /// Area of the surface, `None` if the object can't be sampled as a light
+    fn area(&self) -> Option<f64> {
+        None
+    }
+    /// Point on the surface and the normal there, picked uniformly over the surface's area
+    /// from two numbers in `[0, 1)`. `None` if the object can't be sampled as a light
+    fn sample_surface(&self, _u: (f64, f64)) -> Option<(Vector, Vector)> {
+        None
     }
-    fn set_color(&mut self, color: Color);
+    /// Name of the [`Material`](crate::material::Material) used to shade this object
+    fn material(&self) -> &str {
+        DEFAULT_MATERIAL
+    }
+    fn set_material(&mut self, material: String);
+    /// Takes the object from its own coordinates into the scene's
+    fn transform(&self) -> &Transform;
+    fn set_transform(&mut self, transform: Transform);
     fn into_shape(self) -> Shape;
     fn pos(&self) -> &Vector;
-    fn set_pos(&mut self, pos: Vector);  
+    fn set_pos(&mut self, pos: Vector);
 }
 impl Display for Shape {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Shape::Sphere(sphere) => write!(f, "{}", sphere),
             Shape::Plane(plane) => write!(f, "{}", plane),
+            Shape::Triangle(triangle) => write!(f, "{}", triangle),
+            Shape::Mesh(mesh) => write!(f, "{}", mesh),
+            Shape::Instance(instance) => write!(f, "{}", instance),
+            Shape::Cuboid(cuboid) => write!(f, "{}", cuboid),
+            Shape::Cylinder(cylinder) => write!(f, "{}", cylinder),
+            Shape::Cone(cone) => write!(f, "{}", cone),
+            Shape::Disk(disk) => write!(f, "{}", disk),
+            Shape::Torus(torus) => write!(f, "{}", torus),
+            Shape::Csg(csg) => write!(f, "{}", csg),
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod test_util {
+    use super::*;
+
+    pub fn ray(origin: [f64; 3], direction: [f64; 3]) -> Ray {
+        Ray {
+            origin: origin.into(),
+            direction: direction.into(),
+        }
+    }
+    /// Checks that `ray` first hits `shape` at `t`, where its normal points along `normal`
+    pub fn assert_hit(shape: &impl Object, ray: &Ray, t: f64, normal: Vector) {
+        let hit = shape.hit(ray).expect("missed");
+        assert!((hit.t - t).abs() < 1e-9, "hit at {}, not {t}", hit.t);
+        let got = shape.hit_normal(ray, &hit);
+        assert!((got - normal.normalize()).norm() < 1e-9, "normal {got}");
+    }
+    pub fn assert_miss(shape: &impl Object, ray: &Ray) {
+        if let Intersection::Hit(t) = shape.distance(ray) {
+            panic!("hit at {t}, expected a miss");
+        }
+    }
+    pub fn assert_intervals(got: &[Interval], expected: &[(f64, f64)]) {
+        assert_eq!(got.len(), expected.len(), "{got:?}");
+        for (interval, &(enter, exit)) in got.iter().zip(expected) {
+            assert!((interval.enter - enter).abs() < 1e-9, "{got:?}");
+            assert!((interval.exit - exit).abs() < 1e-9, "{got:?}");
+        }
+    }
+    pub fn assert_bounds(shape: &impl Object, min: [f64; 3], max: [f64; 3]) {
+        let bounds = shape.bounds().expect("unbounded");
+        let close = |a: &Vector, b: [f64; 3]| (a - Vector::from(b)).norm() < 1e-9;
+        assert!(
+            close(&bounds.min, min) && close(&bounds.max, max),
+            "bounds {bounds:?}"
+        );
+    }
+}
@@ -3,34 +3,47 @@ use std::fmt::Display;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    color::{Color, MAGENTA},
+    accel::Aabb,
+    material::DEFAULT_MATERIAL,
     scene::Ray,
-    shapes::{Intersection, Object, Shape, Vector},
+    shapes::{Intersection, Interval, Object, Shape, Transform, Vector},
 };
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Plane {
     pub origin: Vector,
     pub normal: Vector,
-    #[cfg_attr(feature = "serde", serde(with = "crate::color::RgbDef"))]
-    pub color: Color,
+    /// Name of the [`Material`](crate::material::Material) used to shade this shape
+    #[cfg_attr(feature = "serde", serde(default = "crate::shapes::default_material"))]
+    pub material: String,
+    /// Applied on top of the other fields
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Transform::is_identity")
+    )]
+    pub transform: Transform,
 }
 impl Plane {
     pub fn new(origin: Vector, normal: Vector) -> Self {
         Plane {
             origin: origin.normalize(),
             normal: normal.normalize(),
-            color: MAGENTA,
+            material: DEFAULT_MATERIAL.to_string(),
+            transform: Transform::IDENTITY,
         }
     }
-    pub fn new_with_color(origin: Vector, normal: Vector, color: Color) -> Self {
+    pub fn new_with_material(origin: Vector, normal: Vector, material: &str) -> Self {
         let this = Plane::new(origin, normal);
-        Plane { color, ..this }
+        Plane {
+            material: material.to_string(),
+            ..this
+        }
     }
 }
 impl Object for Plane {
     fn distance(&self, ray: &Ray) -> Intersection {
+        let ray = self.transform.ray_to_local(ray);
         let denom = self.normal.dot(&ray.direction);
         if float!(denom -> 0) {
             return Intersection::Miss;
@@ -41,17 +54,54 @@ impl Object for Plane {
         }
         // println!("{t}");
         /* TODO: Check for really big `t` t < 3.0 && */
-        return Intersection::Hit(t);
+        Intersection::Hit(t)
+    }
+    fn normal(&self, _point: Vector) -> Vector {
+        self.transform.normal(&self.normal)
     }
-    fn normal(&self, point: Vector) -> Vector {
-        self.normal
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
+    /// Everything behind the plane, opposite to its normal, counts as inside
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let ray = self.transform.ray_to_local(ray);
+        let denom = self.normal.dot(&ray.direction);
+        let height = (ray.origin - self.origin).dot(&self.normal);
+        if denom == 0.0 {
+            return if height < 0.0 {
+                vec![Interval {
+                    enter: f64::NEG_INFINITY,
+                    exit: f64::INFINITY,
+                }]
+            } else {
+                Vec::new()
+            };
+        }
+        let t = -height / denom;
+        if denom < 0.0 {
+            vec![Interval {
+                enter: t,
+                exit: f64::INFINITY,
+            }]
+        } else {
+            vec![Interval {
+                enter: f64::NEG_INFINITY,
+                exit: t,
+            }]
+        }
     }
 
-    fn color(&self) -> Color {
-        self.color
+    fn material(&self) -> &str {
+        &self.material
+    }
+    fn set_material(&mut self, material: String) {
+        self.material = material;
+    }
+    fn transform(&self) -> &Transform {
+        &self.transform
     }
-    fn set_color(&mut self, color: Color) {
-        self.color = color;
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
     }
     fn into_shape(self) -> Shape {
         Shape::Plane(self)
@@ -3,18 +3,28 @@ use std::fmt::Display;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    color::{Color, MAGENTA},
+    accel::Aabb,
+    material::DEFAULT_MATERIAL,
+    sampling::uniform_sphere,
     scene::Ray,
-    shapes::{Intersection, Object, Vector, Shape},
+    shapes::{solve_quadratic, Intersection, Interval, Object, Shape, Transform, Vector},
+    MIN_T,
 };
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Sphere {
     pub origin: Vector,
     pub radius: f64,
-    #[cfg_attr(feature = "serde", serde(with = "crate::color::RgbDef"))]
-    pub color: Color,
+    /// Name of the [`Material`](crate::material::Material) used to shade this shape
+    #[cfg_attr(feature = "serde", serde(default = "crate::shapes::default_material"))]
+    pub material: String,
+    /// Applied on top of the other fields
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Transform::is_identity")
+    )]
+    pub transform: Transform,
 }
 impl Sphere {
     pub fn new(origin: Vector, radius: f64) -> Self {
@@ -22,16 +32,21 @@ impl Sphere {
         Sphere {
             origin,
             radius,
-            color: MAGENTA,
+            material: DEFAULT_MATERIAL.to_string(),
+            transform: Transform::IDENTITY,
         }
     }
-    pub fn new_with_color(origin: Vector, radius: f64, color: Color) -> Self {
+    pub fn new_with_material(origin: Vector, radius: f64, material: &str) -> Self {
         let this = Sphere::new(origin, radius);
-        Sphere { color, ..this }
+        Sphere {
+            material: material.to_string(),
+            ..this
+        }
     }
 }
 impl Object for Sphere {
     fn intersects(&self, ray: &Ray) -> bool {
+        let ray = &self.transform.ray_to_local(ray);
         /*
           We can check if the ray crosses the sphere
           by moving `dt` along the ray. If we are touching the sphere
@@ -54,7 +69,7 @@ impl Object for Sphere {
         let here_center = maybe_center.metric_distance(&self.origin); */
 
         // Translate coordinates so that the sphere is centered at the origin
-        let ray = ray - self.origin;
+        let ray = &self.transform.ray_to_local(ray) - self.origin;
         /*
           We need to find the point on the ray that is closest to the center of the sphere
           Ray: R(t) = P + t * d
@@ -81,27 +96,72 @@ impl Object for Sphere {
         let discriminant = b.powi(2) - 4.0 * a * c;
 
         if float!(discriminant > 0) {
-            // 2 hit points / ray goes through sphere
-            let t1 = (-b + discriminant.sqrt()) / (2.0 * a);
-            let t2 = (-b - discriminant.sqrt()) / (2.0 * a);
-            Intersection::Hit(t1.min(t2).max(0.0))
-        } else if float!(discriminant -> 0) {
-            // 1 hit point / ray is tangent to sphere
-            Intersection::Hit(-b / (2.0 * a))
+            // 2 hit points / ray goes through sphere (or 1 if it's tangent)
+            let root = discriminant.max(0.0).sqrt();
+            let near = (-b - root) / (2.0 * a);
+            let far = (-b + root) / (2.0 * a);
+            /*
+              The near point is where the ray enters the sphere. If it's behind
+              the origin then the ray starts inside the sphere and leaves through `far`
+            */
+            if near > MIN_T {
+                Intersection::Hit(near)
+            } else if far > MIN_T {
+                Intersection::Hit(far)
+            } else {
+                Intersection::Miss
+            }
         } else {
             // No hit points / ray misses sphere
             Intersection::Miss
         }
     }
     fn normal(&self, point: Vector) -> Vector {
-        let point = (point - self.origin).normalize();
-        point
+        let point = self.transform.point_to_local(&point);
+        self.transform.normal(&(point - self.origin))
+    }
+    fn bounds(&self) -> Option<Aabb> {
+        let r = Vector::repeat(self.radius);
+        Some(
+            self.transform
+                .bounds(&Aabb::new(self.origin - r, self.origin + r)),
+        )
+    }
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let ray = &self.transform.ray_to_local(ray) - self.origin;
+        match solve_quadratic(
+            ray.direction.norm_squared(),
+            2.0 * ray.origin.dot(&ray.direction),
+            ray.origin.norm_squared() - self.radius.powi(2),
+        ) {
+            Some((enter, exit)) if enter < exit => vec![Interval { enter, exit }],
+            _ => Vec::new(),
+        }
     }
-    fn color(&self) -> Color {
-        self.color
+    fn area(&self) -> Option<f64> {
+        let scale = self.transform.uniform_scale()?;
+        Some(4.0 * std::f64::consts::PI * (self.radius * scale).powi(2))
     }
-    fn set_color(&mut self, color: Color) {
-        self.color = color;
+    fn sample_surface(&self, u: (f64, f64)) -> Option<(Vector, Vector)> {
+        // Squashed spheres wouldn't be sampled uniformly, see `area`
+        self.transform.uniform_scale()?;
+        let normal = uniform_sphere(u);
+        Some((
+            self.transform.point(&(self.origin + normal * self.radius)),
+            self.transform.normal(&normal),
+        ))
+    }
+    fn material(&self) -> &str {
+        &self.material
+    }
+    fn set_material(&mut self, material: String) {
+        self.material = material;
+    }
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
     }
     fn into_shape(self) -> Shape {
         Shape::Sphere(self)
@@ -125,4 +185,3 @@ impl Display for Sphere {
         )
     }
 }
-
@@ -0,0 +1,243 @@
+use std::fmt::Display;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accel::Aabb,
+    material::DEFAULT_MATERIAL,
+    scene::Ray,
+    shapes::{Intersection, Interval, Object, Shape, Transform, Vector},
+};
+
+/// Ring made by sweeping a circle of `minor_radius` around a circle of `major_radius`
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Torus {
+    /// Center of the hole
+    pub origin: Vector,
+    /// Direction through the hole
+    pub axis: Vector,
+    /// Distance from the center of the hole to the center of the tube
+    pub major_radius: f64,
+    /// Radius of the tube
+    pub minor_radius: f64,
+    /// Name of the [`Material`](crate::material::Material) used to shade this shape
+    #[cfg_attr(feature = "serde", serde(default = "crate::shapes::default_material"))]
+    pub material: String,
+    /// Applied on top of the other fields
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Transform::is_identity")
+    )]
+    pub transform: Transform,
+}
+impl Torus {
+    pub fn new(origin: Vector, axis: Vector, major_radius: f64, minor_radius: f64) -> Self {
+        debug_assert!(major_radius > 0.0 && minor_radius > 0.0);
+        Torus {
+            origin,
+            axis: axis.normalize(),
+            major_radius,
+            minor_radius,
+            material: DEFAULT_MATERIAL.to_string(),
+            transform: Transform::IDENTITY,
+        }
+    }
+    pub fn new_with_material(
+        origin: Vector,
+        axis: Vector,
+        major_radius: f64,
+        minor_radius: f64,
+        material: &str,
+    ) -> Self {
+        let this = Torus::new(origin, axis, major_radius, minor_radius);
+        Torus {
+            material: material.to_string(),
+            ..this
+        }
+    }
+    /// Takes the torus from lying on the XY plane around the origin into the scene
+    fn frame(&self) -> Transform {
+        self.transform * Transform::from_axis(self.origin, self.axis)
+    }
+    /// Every distance at which the ray crosses the surface, in no particular order
+    fn crossings(&self, ray: &Ray) -> Vec<f64> {
+        let ray = self.frame().ray_to_local(ray);
+        let scale = ray.direction.norm();
+        if scale == 0.0 {
+            return Vec::new();
+        }
+        let d = ray.direction / scale;
+        /*
+          The coefficients get huge far from the torus, losing precision,
+          so start from where the ray gets close enough to possibly hit it
+        */
+        let reach = self.major_radius + self.minor_radius;
+        let skip = (ray.origin.norm() - reach).max(0.0);
+        let o = ray.origin + d * skip;
+        /*
+          (x² + y² + z² + R² - r²)² = 4R²(x² + y²)
+          With |d| = 1, substituting the ray gives a quartic in t:
+            (t² + 2(o·d)t + k)² - 4R²((ox + t dx)² + (oy + t dy)²) = 0
+          where k = |o|² + R² - r²
+        */
+        let r2 = self.major_radius.powi(2);
+        let od = o.dot(&d);
+        let k = o.norm_squared() + r2 - self.minor_radius.powi(2);
+        let coefficients = [
+            k * k - 4.0 * r2 * (o.x * o.x + o.y * o.y),
+            4.0 * od * k - 8.0 * r2 * (o.x * d.x + o.y * d.y),
+            2.0 * k + 4.0 * od * od - 4.0 * r2 * (d.x * d.x + d.y * d.y),
+            4.0 * od,
+        ];
+        solve_quartic(coefficients)
+            .into_iter()
+            .map(|t| (t + skip) / scale)
+            .collect()
+    }
+}
+impl Object for Torus {
+    fn distance(&self, ray: &Ray) -> Intersection {
+        Intersection::nearest(self.crossings(ray))
+    }
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        Interval::alternating(self.crossings(ray))
+    }
+    fn normal(&self, point: Vector) -> Vector {
+        let frame = self.frame();
+        let p = frame.point_to_local(&point);
+        // Away from the closest point on the circle running through the tube
+        let ring = Vector::new(p.x, p.y, 0.0)
+            .try_normalize(0.0)
+            .unwrap_or_else(|| Vector::new(1.0, 0.0, 0.0))
+            * self.major_radius;
+        frame.normal(&(p - ring))
+    }
+    fn bounds(&self) -> Option<Aabb> {
+        let reach = self.major_radius + self.minor_radius;
+        let r = self.minor_radius;
+        Some(self.frame().bounds(&Aabb::new(
+            Vector::new(-reach, -reach, -r),
+            Vector::new(reach, reach, r),
+        )))
+    }
+    fn material(&self) -> &str {
+        &self.material
+    }
+    fn set_material(&mut self, material: String) {
+        self.material = material;
+    }
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+    fn into_shape(self) -> Shape {
+        Shape::Torus(self)
+    }
+    fn pos(&self) -> &Vector {
+        &self.origin
+    }
+    fn set_pos(&mut self, pos: Vector) {
+        self.origin = pos;
+    }
+}
+impl Display for Torus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Torus of radii {major} and {minor} around ({ox}, {oy}, {oz})",
+            major = self.major_radius,
+            minor = self.minor_radius,
+            ox = self.origin[0],
+            oy = self.origin[1],
+            oz = self.origin[2],
+        )
+    }
+}
+
+/// Real roots of `t⁴ + c₃t³ + c₂t² + c₁t + c₀`, given as `[c₀, c₁, c₂, c₃]`.
+///
+/// They are the eigenvalues of the polynomial's companion matrix,
+/// polished with a few steps of Newton's method.
+fn solve_quartic(c: [f64; 4]) -> Vec<f64> {
+    let companion = na::Matrix4::new(
+        0.0, 0.0, 0.0, -c[0], //
+        1.0, 0.0, 0.0, -c[1], //
+        0.0, 1.0, 0.0, -c[2], //
+        0.0, 0.0, 1.0, -c[3],
+    );
+    let polynomial = |t: f64| (((t + c[3]) * t + c[2]) * t + c[1]) * t + c[0];
+    let derivative = |t: f64| ((4.0 * t + 3.0 * c[3]) * t + 2.0 * c[2]) * t + c[1];
+    companion
+        .complex_eigenvalues()
+        .iter()
+        // Grazing hits give a pair of roots with a tiny imaginary part
+        .filter(|root| root.im.abs() <= 1e-6 * root.re.abs().max(1.0))
+        .map(|root| {
+            let mut t = root.re;
+            for _ in 0..3 {
+                let slope = derivative(t);
+                if slope == 0.0 {
+                    break;
+                }
+                t -= polynomial(t) / slope;
+            }
+            t
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::test_util::{assert_bounds, assert_hit, assert_intervals, assert_miss, ray};
+
+    /// Lying on the XY plane, the tube goes from 1 to 3 away from the center
+    fn torus() -> Torus {
+        Torus::new(Vector::zeros(), Vector::new(0.0, 0.0, 1.0), 2.0, 1.0)
+    }
+
+    #[test]
+    fn hits_the_outside_of_the_tube() {
+        let ray = ray([-10.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        assert_hit(&torus(), &ray, 7.0, Vector::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn hits_the_top_of_the_tube() {
+        let ray = ray([2.0, 0.0, 10.0], [0.0, 0.0, -1.0]);
+        assert_hit(&torus(), &ray, 9.0, Vector::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn hits_the_inside_of_the_hole() {
+        let ray = ray([0.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        assert_hit(&torus(), &ray, 1.0, Vector::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn misses_through_the_hole() {
+        assert_miss(&torus(), &ray([0.0, 0.0, 10.0], [0.0, 0.0, -1.0]));
+        assert_miss(&torus(), &ray([-10.0, 0.0, 1.5], [1.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn works_with_unnormalized_directions() {
+        let ray = ray([-10.0, 0.0, 0.0], [0.5, 0.0, 0.0]);
+        assert_hit(&torus(), &ray, 14.0, Vector::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn inside_both_sides_of_the_tube() {
+        let ray = ray([-10.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        assert_intervals(&torus().intervals(&ray), &[(7.0, 9.0), (11.0, 13.0)]);
+    }
+
+    #[test]
+    fn bounds_reach_the_outside_of_the_tube() {
+        assert_bounds(&torus(), [-3.0, -3.0, -1.0], [3.0, 3.0, 1.0]);
+    }
+}
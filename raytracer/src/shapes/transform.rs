@@ -0,0 +1,282 @@
+use std::{fmt::Display, ops::Mul};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accel::Aabb,
+    sampling::orthonormal_basis,
+    scene::Ray,
+    shapes::{Vector, ZP},
+};
+
+/// Tolerance used when checking whether a matrix is a plain rotation and scale
+const EPSILON: f64 = 1e-9;
+
+/// Affine transform taking an object from its own coordinates into the scene's.
+///
+/// Stored as the linear part of the 4x4 matrix plus the translation, along with the inverse
+/// of the linear part so that rays can be brought into object space without inverting
+/// anything while rendering. Scene files use translation, rotation and scale like
+/// `{"translation": [1, 0, 0], "rotation": [0, 0, 90], "scale": [2, 2, 2]}`,
+/// or a row-major `{"matrix": [[...], [...], [...], [0, 0, 0, 1]]}` for anything else.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "TransformDef", into = "TransformDef")
+)]
+pub struct Transform {
+    linear: na::Matrix3<f64>,
+    translation: Vector,
+    inverse: na::Matrix3<f64>,
+}
+
+#[derive(Debug)]
+pub enum TransformError {
+    /// The transform squashes space flat and can't be undone
+    Singular,
+    /// The last row of the matrix isn't `[0, 0, 0, 1]`
+    NotAffine,
+    /// Both a matrix and translation, rotation or scale were given
+    MatrixAndTrs,
+}
+impl Display for TransformError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransformError::Singular => write!(f, "transform can't be inverted"),
+            TransformError::NotAffine => {
+                write!(f, "the last row of a transform matrix must be [0, 0, 0, 1]")
+            }
+            TransformError::MatrixAndTrs => write!(
+                f,
+                "a transform takes either a matrix or translation, rotation and scale"
+            ),
+        }
+    }
+}
+impl std::error::Error for TransformError {}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::IDENTITY
+    }
+}
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        linear: na::Matrix3::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0),
+        translation: na::Vector3::new(0.0, 0.0, 0.0),
+        inverse: na::Matrix3::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0),
+    };
+
+    /// Scales first, then rotates around the X, Y and Z axes (in degrees, in that order),
+    /// then translates
+    pub fn new(
+        translation: Vector,
+        rotation: Vector,
+        scale: Vector,
+    ) -> Result<Self, TransformError> {
+        let r = rotation.map(f64::to_radians);
+        let rotation = na::Rotation3::from_euler_angles(r.x, r.y, r.z);
+        Transform::from_parts(
+            rotation.matrix() * na::Matrix3::from_diagonal(&scale),
+            translation,
+        )
+    }
+    pub fn translation(translation: Vector) -> Self {
+        Transform {
+            translation,
+            ..Transform::IDENTITY
+        }
+    }
+    /// Rotates the Z axis onto `axis` and moves the origin to `origin`, for shapes
+    /// that are easier to describe standing along Z
+    pub fn from_axis(origin: Vector, axis: Vector) -> Self {
+        let axis = axis.try_normalize(0.0).unwrap_or(ZP);
+        let (tangent, bitangent) = orthonormal_basis(&axis);
+        let rotation = na::Matrix3::from_columns(&[tangent, bitangent, axis]);
+        Transform {
+            linear: rotation,
+            translation: origin,
+            inverse: rotation.transpose(),
+        }
+    }
+    /// Builds a transform from a 4x4 matrix whose last row is `[0, 0, 0, 1]`
+    pub fn from_matrix(matrix: na::Matrix4<f64>) -> Result<Self, TransformError> {
+        if matrix.row(3) != na::RowVector4::new(0.0, 0.0, 0.0, 1.0) {
+            return Err(TransformError::NotAffine);
+        }
+        Transform::from_parts(
+            matrix.fixed_slice::<3, 3>(0, 0).into_owned(),
+            matrix.fixed_slice::<3, 1>(0, 3).into_owned(),
+        )
+    }
+    fn from_parts(linear: na::Matrix3<f64>, translation: Vector) -> Result<Self, TransformError> {
+        let inverse = linear.try_inverse().ok_or(TransformError::Singular)?;
+        Ok(Transform {
+            linear,
+            translation,
+            inverse,
+        })
+    }
+
+    pub fn matrix(&self) -> na::Matrix4<f64> {
+        let mut matrix = self.linear.to_homogeneous();
+        matrix
+            .fixed_slice_mut::<3, 1>(0, 3)
+            .copy_from(&self.translation);
+        matrix
+    }
+    /// Where the object's origin ends up
+    pub fn offset(&self) -> &Vector {
+        &self.translation
+    }
+    pub fn set_offset(&mut self, offset: Vector) {
+        self.translation = offset;
+    }
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::IDENTITY
+    }
+    /// Factor every length is multiplied by, `None` if the transform stretches
+    /// some directions more than others
+    pub fn uniform_scale(&self) -> Option<f64> {
+        let gram = self.linear.transpose() * self.linear;
+        let scale2 = gram.trace() / 3.0;
+        let uniform = (gram - na::Matrix3::from_diagonal_element(scale2))
+            .abs()
+            .max();
+        (uniform <= EPSILON * scale2).then(|| scale2.sqrt())
+    }
+
+    pub fn point(&self, point: &Vector) -> Vector {
+        self.linear * point + self.translation
+    }
+    pub fn vector(&self, vector: &Vector) -> Vector {
+        self.linear * vector
+    }
+    /// Transforms a surface normal by the inverse transpose so that it stays perpendicular
+    /// to the surface, normalizing it
+    pub fn normal(&self, normal: &Vector) -> Vector {
+        (self.inverse.transpose() * normal).normalize()
+    }
+    pub fn point_to_local(&self, point: &Vector) -> Vector {
+        self.inverse * (point - self.translation)
+    }
+    /// Brings a ray into object space. The direction isn't normalized again, so distances
+    /// along the ray are the same in both spaces.
+    pub fn ray_to_local(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.point_to_local(&ray.origin),
+            direction: self.inverse * ray.direction,
+        }
+    }
+    /// Box around the transformed corners of `aabb`
+    pub fn bounds(&self, aabb: &Aabb) -> Aabb {
+        if aabb.is_empty() {
+            return *aabb;
+        }
+        let corners = aabb.corners().map(|corner| self.point(&corner));
+        Aabb::from_points(&corners)
+    }
+
+    /// Translation, rotation in degrees and scale giving the same transform,
+    /// `None` if it shears or mirrors
+    fn decompose(&self) -> Option<(Vector, Vector, Vector)> {
+        let scale = Vector::from_iterator(self.linear.column_iter().map(|c| c.norm()));
+        if scale.min() <= EPSILON {
+            return None;
+        }
+        let rotation = self.linear * na::Matrix3::from_diagonal(&scale.map(|s| 1.0 / s));
+        if rotation.determinant() <= 0.0
+            || (rotation.transpose() * rotation - na::Matrix3::identity())
+                .abs()
+                .max()
+                > EPSILON
+        {
+            return None;
+        }
+        let (x, y, z) = na::Rotation3::from_matrix_unchecked(rotation).euler_angles();
+        // Rounded so that scene files show 30 rather than 29.999999999999996
+        let rotation = Vector::new(x, y, z).map(|r| (r.to_degrees() * 1e9).round() / 1e9 + 0.0);
+        Some((self.translation, rotation, scale))
+    }
+}
+/// `a * b` applies `b` first, then `a`
+impl Mul for Transform {
+    type Output = Transform;
+    fn mul(self, rhs: Transform) -> Self::Output {
+        Transform {
+            linear: self.linear * rhs.linear,
+            translation: self.point(&rhs.translation),
+            inverse: rhs.inverse * self.inverse,
+        }
+    }
+}
+
+/// What gets stored in a scene file, either the matrix or its parts
+#[cfg(feature = "serde")]
+#[derive(Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransformDef {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    translation: Option<Vector>,
+    /// Degrees around the X, Y and Z axes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rotation: Option<Vector>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scale: Option<Vector>,
+    /// Rows of the 4x4 matrix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    matrix: Option<[[f64; 4]; 4]>,
+}
+#[cfg(feature = "serde")]
+impl TryFrom<TransformDef> for Transform {
+    type Error = TransformError;
+    fn try_from(def: TransformDef) -> Result<Self, Self::Error> {
+        match def {
+            TransformDef {
+                translation: None,
+                rotation: None,
+                scale: None,
+                matrix: Some(rows),
+            } => Transform::from_matrix(na::Matrix4::from_fn(|r, c| rows[r][c])),
+            TransformDef {
+                matrix: Some(_), ..
+            } => Err(TransformError::MatrixAndTrs),
+            TransformDef {
+                translation,
+                rotation,
+                scale,
+                matrix: None,
+            } => Transform::new(
+                translation.unwrap_or_default(),
+                rotation.unwrap_or_default(),
+                scale.unwrap_or_else(|| Vector::repeat(1.0)),
+            ),
+        }
+    }
+}
+#[cfg(feature = "serde")]
+impl From<Transform> for TransformDef {
+    fn from(transform: Transform) -> Self {
+        let decomposed = transform.decompose().filter(|&(t, r, s)| {
+            Transform::new(t, r, s)
+                .is_ok_and(|rebuilt| (rebuilt.matrix() - transform.matrix()).abs().max() <= EPSILON)
+        });
+        match decomposed {
+            Some((translation, rotation, scale)) => TransformDef {
+                translation: (translation != Vector::zeros()).then_some(translation),
+                rotation: (rotation != Vector::zeros()).then_some(rotation),
+                scale: (scale != Vector::repeat(1.0)).then_some(scale),
+                matrix: None,
+            },
+            None => {
+                let matrix = transform.matrix();
+                TransformDef {
+                    matrix: Some([0, 1, 2, 3].map(|r| [0, 1, 2, 3].map(|c| matrix[(r, c)]))),
+                    ..TransformDef::default()
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt::Display;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accel::Aabb,
+    material::DEFAULT_MATERIAL,
+    scene::Ray,
+    shapes::{Intersection, Object, Shape, Transform, Vector},
+    MIN_T,
+};
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Triangle {
+    pub a: Vector,
+    pub b: Vector,
+    pub c: Vector,
+    /// Name of the [`Material`](crate::material::Material) used to shade this shape
+    #[cfg_attr(feature = "serde", serde(default = "crate::shapes::default_material"))]
+    pub material: String,
+    /// Applied on top of the other fields
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Transform::is_identity")
+    )]
+    pub transform: Transform,
+}
+impl Triangle {
+    pub fn new(a: Vector, b: Vector, c: Vector) -> Self {
+        Triangle {
+            a,
+            b,
+            c,
+            material: DEFAULT_MATERIAL.to_string(),
+            transform: Transform::IDENTITY,
+        }
+    }
+    pub fn new_with_material(a: Vector, b: Vector, c: Vector, material: &str) -> Self {
+        let this = Triangle::new(a, b, c);
+        Triangle {
+            material: material.to_string(),
+            ..this
+        }
+    }
+    /// Vertices after applying the transform
+    fn corners(&self) -> [Vector; 3] {
+        [&self.a, &self.b, &self.c].map(|v| self.transform.point(v))
+    }
+}
+impl Object for Triangle {
+    fn distance(&self, ray: &Ray) -> Intersection {
+        let ray = self.transform.ray_to_local(ray);
+        match moller_trumbore(&ray, &self.a, &self.b, &self.c) {
+            Some((t, _, _)) => Intersection::Hit(t),
+            None => Intersection::Miss,
+        }
+    }
+    fn normal(&self, _point: Vector) -> Vector {
+        self.transform
+            .normal(&(self.b - self.a).cross(&(self.c - self.a)))
+    }
+    fn bounds(&self) -> Option<Aabb> {
+        let [a, b, c] = self.corners();
+        // Padded like a mesh's triangles, so that flat boxes can still be hit
+        Some(Aabb::from_points([&a, &b, &c]).pad(MIN_T))
+    }
+    fn area(&self) -> Option<f64> {
+        let [a, b, c] = self.corners();
+        Some((b - a).cross(&(c - a)).norm() / 2.0)
+    }
+    fn sample_surface(&self, (u, v): (f64, f64)) -> Option<(Vector, Vector)> {
+        // Fold the unit square in half so the point lands inside the triangle
+        let (u, v) = if u + v > 1.0 {
+            (1.0 - u, 1.0 - v)
+        } else {
+            (u, v)
+        };
+        let [a, b, c] = self.corners();
+        let point = a + (b - a) * u + (c - a) * v;
+        Some((point, self.normal(point)))
+    }
+    fn material(&self) -> &str {
+        &self.material
+    }
+    fn set_material(&mut self, material: String) {
+        self.material = material;
+    }
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+    fn into_shape(self) -> Shape {
+        Shape::Triangle(self)
+    }
+    fn pos(&self) -> &Vector {
+        &self.a
+    }
+    fn set_pos(&mut self, pos: Vector) {
+        let offset = pos - self.a;
+        self.a += offset;
+        self.b += offset;
+        self.c += offset;
+    }
+}
+
+/// Möller–Trumbore ray/triangle intersection.
+///
+/// Returns the distance along the ray and the barycentric coordinates `(u, v)`
+/// of the hit, so that it lies at `a + u(b - a) + v(c - a)`.
+pub(crate) fn moller_trumbore(
+    ray: &Ray,
+    a: &Vector,
+    b: &Vector,
+    c: &Vector,
+) -> Option<(f64, f64, f64)> {
+    line_triangle(ray, a, b, c).filter(|&(t, _, _)| t > MIN_T)
+}
+
+/// Like [`moller_trumbore`] but also finds hits behind the ray's origin
+pub(crate) fn line_triangle(
+    ray: &Ray,
+    a: &Vector,
+    b: &Vector,
+    c: &Vector,
+) -> Option<(f64, f64, f64)> {
+    /*
+      Solve R(t) = a + u(b - a) + v(c - a) for t, u and v using Cramer's rule:
+        [-d  e1  e2] [t u v]ᵀ = P - a
+    */
+    let e1 = b - a;
+    let e2 = c - a;
+    let p = ray.direction.cross(&e2);
+    let det = e1.dot(&p);
+    // The ray is parallel to the triangle
+    if det.abs() < f64::EPSILON {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let s = ray.origin - a;
+    let u = s.dot(&p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&e1);
+    let v = ray.direction.dot(&q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = e2.dot(&q) * inv_det;
+    Some((t, u, v))
+}
+
+impl Display for Triangle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "△(({ax}, {ay}, {az}), ({bx}, {by}, {bz}), ({cx}, {cy}, {cz}))",
+            ax = self.a[0],
+            ay = self.a[1],
+            az = self.a[2],
+            bx = self.b[0],
+            by = self.b[1],
+            bz = self.b[2],
+            cx = self.c[0],
+            cy = self.c[1],
+            cz = self.c[2],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_triangles_get_boxes_with_some_thickness() {
+        let triangle = Triangle::new(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        );
+        let bounds = triangle.bounds().unwrap();
+        assert!(bounds.extent().z > 0.0);
+        assert!(bounds.contains(&Vector::new(0.5, 0.25, 0.0)));
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+
+use glium::glutin::{
+    dpi::PhysicalPosition,
+    event::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent},
+};
+use rt::{scene::Camera, shapes::Vector};
+
+/// Radians turned for every pixel the mouse moves
+const MOUSE_SENSITIVITY: f64 = 0.004;
+/// Radians turned per second when orbiting with the keyboard
+const ORBIT_SPEED: f64 = 1.0;
+/// Closest the camera may look to straight up or down, as the cosine of the angle
+const MAX_PITCH_COS: f64 = 0.995;
+
+pub const HELP: &str = "Controls: drag with the left mouse button to look around, \
+    WASD to move, Q/E to go down/up, scroll to change speed or zoom, \
+    Tab to switch between fly and orbit, P to save the camera to the scene file";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Move freely, the mouse turns the camera in place
+    Fly,
+    /// Circle around the target, the mouse moves the camera around it
+    Orbit,
+}
+
+/// What the window should do after an event, besides moving the camera
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    SaveCamera,
+}
+
+/// Turns keyboard and mouse input into camera movement
+pub struct CameraControls {
+    mode: Mode,
+    held: HashSet<VirtualKeyCode>,
+    dragging: bool,
+    cursor: Option<PhysicalPosition<f64>>,
+    /// Mouse movement in pixels since the last update
+    look: (f64, f64),
+    /// Scrolled lines since the last update
+    scroll: f64,
+    /// Flying speed in target distances per second
+    speed: f64,
+}
+impl CameraControls {
+    pub fn new() -> Self {
+        CameraControls {
+            mode: Mode::Fly,
+            held: HashSet::new(),
+            dragging: false,
+            cursor: None,
+            look: (0.0, 0.0),
+            scroll: 0.0,
+            speed: 1.0,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent) -> Option<Action> {
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                let key = input.virtual_keycode?;
+                match input.state {
+                    ElementState::Pressed => {
+                        // Ignore key repeats for one-off actions
+                        if !self.held.insert(key) {
+                            return None;
+                        }
+                        match key {
+                            VirtualKeyCode::Tab => {
+                                self.mode = match self.mode {
+                                    Mode::Fly => Mode::Orbit,
+                                    Mode::Orbit => Mode::Fly,
+                                };
+                                println!("Camera mode: {:?}", self.mode);
+                            }
+                            VirtualKeyCode::P => return Some(Action::SaveCamera),
+                            _ => {}
+                        }
+                    }
+                    ElementState::Released => {
+                        self.held.remove(&key);
+                    }
+                }
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.dragging = *state == ElementState::Pressed;
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                if let (true, Some(last)) = (self.dragging, self.cursor) {
+                    self.look.0 += position.x - last.x;
+                    self.look.1 += position.y - last.y;
+                }
+                self.cursor = Some(*position);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y as f64,
+                    MouseScrollDelta::PixelDelta(position) => position.y / 20.0,
+                };
+            }
+            // Keys released while unfocused never arrive
+            WindowEvent::Focused(false) => {
+                self.held.clear();
+                self.dragging = false;
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Moves `camera` according to the input received in the last `dt` seconds.
+    /// `None` if it didn't move.
+    pub fn update(&mut self, camera: &Camera, dt: f64) -> Option<Camera> {
+        let (dx, dy) = std::mem::take(&mut self.look);
+        let scroll = std::mem::take(&mut self.scroll);
+        let axis = |positive, negative| {
+            self.held.contains(&positive) as i32 as f64
+                - self.held.contains(&negative) as i32 as f64
+        };
+        let forward_input = axis(VirtualKeyCode::W, VirtualKeyCode::S);
+        let right_input = axis(VirtualKeyCode::D, VirtualKeyCode::A);
+        let up_input = axis(VirtualKeyCode::E, VirtualKeyCode::Q);
+        if dx == 0.0
+            && dy == 0.0
+            && scroll == 0.0
+            && forward_input == 0.0
+            && right_input == 0.0
+            && up_input == 0.0
+        {
+            return None;
+        }
+
+        let origin = *camera.origin();
+        let target = *camera.target();
+        let guide = camera.up_guide().normalize();
+        let distance = (target - origin).norm().max(1e-3);
+        let (origin, target) = match self.mode {
+            Mode::Fly => {
+                self.speed *= 1.1f64.powf(scroll);
+                let forward = look_around(
+                    *camera.forward(),
+                    &guide,
+                    camera.right(),
+                    dx * MOUSE_SENSITIVITY,
+                    dy * MOUSE_SENSITIVITY,
+                );
+                let right = guide
+                    .cross(&forward)
+                    .try_normalize(1e-9)
+                    .unwrap_or(*camera.right());
+                let step = (forward * forward_input + right * right_input + guide * up_input)
+                    * self.speed
+                    * distance
+                    * dt;
+                let origin = origin + step;
+                (origin, origin + forward * distance)
+            }
+            Mode::Orbit => {
+                // Dragging spins the scene along with the mouse, the keys move the camera
+                let back = -look_around(
+                    *camera.forward(),
+                    &guide,
+                    camera.right(),
+                    dx * MOUSE_SENSITIVITY - right_input * ORBIT_SPEED * dt,
+                    dy * MOUSE_SENSITIVITY + up_input * ORBIT_SPEED * dt,
+                );
+                let distance = distance * 0.9f64.powf(scroll) * (1.0 - forward_input * dt).max(0.1);
+                (target + back * distance, target)
+            }
+        };
+        Some(camera.with_view(origin, target))
+    }
+}
+
+/// Turns `forward` by `yaw` radians to the right around `guide`
+/// and `pitch` radians downwards, stopping short of looking along `guide`
+fn look_around(forward: Vector, guide: &Vector, right: &Vector, yaw: f64, pitch: f64) -> Vector {
+    let forward = rotate(&forward, guide, yaw);
+    let right = rotate(right, guide, yaw);
+    let pitched = rotate(&forward, &right, pitch);
+    if pitched.dot(guide).abs() < MAX_PITCH_COS {
+        pitched
+    } else {
+        forward
+    }
+}
+
+/// Rotates `v` by `angle` radians around the unit vector `axis` (Rodrigues' formula)
+fn rotate(v: &Vector, axis: &Vector, angle: f64) -> Vector {
+    let (sin, cos) = angle.sin_cos();
+    v * cos + axis.cross(v) * sin + axis * axis.dot(v) * (1.0 - cos)
+}
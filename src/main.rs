@@ -1,86 +1,165 @@
 extern crate raytracer as rt;
 
-use std::path::PathBuf;
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use clap::Parser;
 use glium::{
     glutin::{self, dpi::LogicalSize},
     uniform, Display, Surface,
 };
-use image::{ImageBuffer, Rgb};
+use image::{codecs::hdr::HdrEncoder, ImageBuffer, ImageFormat, Rgb, Rgb32FImage};
 use rt::{
-    scene::{render, Camera, parallel_render},
+    color::{linear_to_srgb, Tonemap, WHITE},
+    integrator::IntegratorKind,
+    material::{Material, Materials},
+    sampling::SamplerKind,
+    scene::{
+        parallel_render, parallel_render_target, Accumulator, AdaptiveSampling, Camera,
+        Gray32FImage, Light, RenderSettings, RenderTarget, Scene,
+    },
     shapes::*,
 };
 
+use controls::{Action, CameraControls};
+
+mod controls;
 mod window;
 
-fn render_texture(
-    width: u32,
-    height: u32,
+/// Uploads a linear render after exposure and tone mapping. The texture stays linear,
+/// the window's sRGB framebuffer encodes the colors when drawing.
+fn upload_texture(
     display: &Display,
-    objects: &[Shape],
-) -> glium::texture::SrgbTexture2d {
-    let origin = Vector::new(0.0, 0.0, -1.0);
-    let upguide = Vector::new(0.0, -1.0, -1.0);
-    let camera = Camera::new(60.0, Vector::zeros(), origin);
-
-    let mut framebuffer = ImageBuffer::new(width, height);
-    // This panics for some reason:
-    // framebuffer.save("out.png").unwrap();
-
-    let dt: f64 = std::time::UNIX_EPOCH.elapsed().unwrap().as_millis() as _;
-    let l1 = (dt * 0.001).sin() * 1.0;
-    let light = Vector::new(0.0, l1, 0.0);
-
-    // render(&mut framebuffer, &objects, &camera, &light);
-    parallel_render(&mut framebuffer, &objects, &camera, &light);
-
+    mut image: Rgb32FImage,
+    settings: &RenderSettings,
+) -> glium::texture::Texture2d {
+    for pixel in image.pixels_mut() {
+        *pixel = settings.develop(*pixel);
+    }
     let raw_image = {
-        let dims = framebuffer.dimensions();
-        // glium::texture::RawImage2d::from_raw_rgb_reversed(&framebuffer.into_raw(), dims)
-        glium::texture::RawImage2d::from_raw_rgb(framebuffer.into_raw(), dims)
+        let dims = image.dimensions();
+        glium::texture::RawImage2d::from_raw_rgb(image.into_raw(), dims)
     };
 
-    let texture = glium::texture::SrgbTexture2d::new(display, raw_image).unwrap();
-
-    return texture;
+    glium::texture::Texture2d::with_format(
+        display,
+        raw_image,
+        glium::texture::UncompressedFloatFormat::F16F16F16,
+        glium::texture::MipmapsOption::NoMipmap,
+    )
+    .unwrap()
 }
 
 fn file_render(
     width: u32,
     height: u32,
-    objects: &[Shape],
-){
-    let origin = Vector::new(0.0, 0.0, -1.0);
-    let upguide = Vector::new(0.0, -1.0, -1.0);
-    let camera = Camera::new(60.0, Vector::zeros(), origin);
-
-    let mut framebuffer = ImageBuffer::new(width, height);
-    // This panics for some reason:
-    // framebuffer.save("out.png").unwrap();
-
-    let dt: f64 = std::time::UNIX_EPOCH.elapsed().unwrap().as_millis() as _;
-    let l1 = (dt * 0.0001).sin() * 1.0;
-    // let l2 = (dt * 0.0001).cos() * 0.0;
-    let light = Vector::new(0.0, l1, 0.0);
+    scene: &Scene,
+    output: &Path,
+    aovs: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let framebuffer = if aovs || scene.settings.denoise.is_some() {
+        let mut target = RenderTarget::new(width, height);
+        let integrator = scene.settings.integrator.build(scene);
+        parallel_render_target(&mut target, scene, integrator.as_ref());
+        if aovs {
+            save_aovs(&target, output)?;
+        }
+        match scene.settings.denoise {
+            Some(denoiser) => denoiser.apply(&target),
+            None => target.beauty,
+        }
+    } else {
+        let mut framebuffer = ImageBuffer::new(width, height);
+        // render(&mut framebuffer, scene);
+        parallel_render(&mut framebuffer, scene);
+        framebuffer
+    };
 
-    // render(&mut framebuffer, &objects, &camera, &light);
-    parallel_render(&mut framebuffer, &objects, &camera, &light);
+    let extension = output
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase());
+    // Float formats keep the unclamped linear light, grading is left to the compositor
+    let saved = match extension.as_deref() {
+        Some("exr") => Some(framebuffer.save_with_format(output, ImageFormat::OpenExr)),
+        Some("hdr") => Some(save_radiance_hdr(&framebuffer, output)),
+        _ => None,
+    };
+    if let Some(saved) = saved {
+        saved.map_err(|e| format!("Failed to write {}: {e}", output.display()))?;
+        return Ok(());
+    }
 
     let mut framebuffer2 = ImageBuffer::new(width, height);
     for (x, y, pixel) in framebuffer.enumerate_pixels() {
-        let channels: [u8; 3] = [
-            (pixel.0[0] * 256.0) as _,
-            (pixel.0[1] * 256.0) as _,
-            (pixel.0[2] * 256.0) as _,
-        ];
+        let pixel = scene.settings.develop(*pixel);
+        let channels: [u8; 3] = pixel
+            .0
+            .map(|c| (linear_to_srgb(c) * 255.0).round().clamp(0.0, 255.0) as u8);
         let pixel = Rgb(channels);
         let pixel2 = framebuffer2.get_pixel_mut(x, y);
         *pixel2 = pixel;
     }
-    
-    framebuffer2.save(format!("screenshots/out_{dt}.png")).unwrap();
+
+    framebuffer2
+        .save(output)
+        .map_err(|e| format!("Failed to write {}: {e}", output.display()))?;
+    Ok(())
+}
+
+/// Writes every buffer but the beauty image next to `output` as OpenEXR,
+/// `render.png` gets `render.depth.exr`, `render.normal.exr` and so on
+fn save_aovs(target: &RenderTarget, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let gray = |image: &Gray32FImage| {
+        Rgb32FImage::from_fn(image.width(), image.height(), |x, y| {
+            Rgb([image.get_pixel(x, y).0[0]; 3])
+        })
+    };
+    let object_id = Rgb32FImage::from_fn(
+        target.object_id.width(),
+        target.object_id.height(),
+        |x, y| Rgb([target.object_id.get_pixel(x, y).0[0] as f32; 3]),
+    );
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    for (name, image) in [
+        ("depth", &gray(&target.depth)),
+        ("normal", &target.normal),
+        ("albedo", &target.albedo),
+        ("object_id", &object_id),
+        ("shadow", &gray(&target.shadow)),
+    ] {
+        let path = output.with_file_name(format!("{stem}.{name}.exr"));
+        image
+            .save_with_format(&path, ImageFormat::OpenExr)
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Writes a float image in the Radiance `.hdr` format
+fn save_radiance_hdr(framebuffer: &Rgb32FImage, output: &Path) -> image::ImageResult<()> {
+    let file = BufWriter::new(File::create(output)?);
+    let (width, height) = framebuffer.dimensions();
+    let pixels: Vec<_> = framebuffer.pixels().copied().collect();
+    HdrEncoder::new(file).encode(&pixels, width as usize, height as usize)
+}
+
+/// Replaces the camera in the scene file, leaving the rest of the file as it was
+fn save_camera(path: &Path, camera: &Camera) -> Result<(), Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let mut value: serde_json::Value = serde_json::from_str(&text)
+        .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+    value
+        .as_object_mut()
+        .ok_or_else(|| format!("{} doesn't contain a scene", path.display()))?
+        .insert("camera".to_string(), serde_json::to_value(camera)?);
+    std::fs::write(path, serde_json::to_string_pretty(&value)?)
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    Ok(())
 }
 
 #[derive(Parser)]
@@ -93,20 +172,104 @@ struct Args {
 
     #[clap(long, default_value = "scene.json", value_parser)]
     scene: PathBuf,
+
+    /// How many times a ray may bounce off reflective surfaces, overrides the scene's setting
+    #[clap(long)]
+    max_depth: Option<u32>,
+
+    /// Rendering algorithm, overrides the scene's setting. One of `whitted`, `path_tracer`,
+    /// or the debug views `normals`, `depth`, `albedo` and `object_id`
+    #[clap(long)]
+    integrator: Option<IntegratorKind>,
+
+    /// Samples per pixel, overrides the scene's setting
+    #[clap(long)]
+    samples: Option<u32>,
+
+    /// How samples are spread over a pixel, one of `random`, `stratified`, `halton`
+    /// or `sobol`. Overrides the scene's setting
+    #[clap(long)]
+    sampler: Option<SamplerKind>,
+
+    /// Stop sampling a pixel once its relative noise falls below this threshold,
+    /// `--samples` becomes the most samples taken
+    #[clap(long)]
+    adaptive: Option<f32>,
+
+    /// Brightness adjustment in stops, overrides the scene's setting
+    #[clap(long, allow_hyphen_values = true)]
+    exposure: Option<f32>,
+
+    /// Tone mapping operator, one of `clamp`, `reinhard` or `aces`. Overrides the scene's setting
+    #[clap(long)]
+    tonemap: Option<Tonemap>,
+
+    /// Render a single image to this path and exit without opening a window.
+    /// `.exr` and `.hdr` files keep the unclamped linear light
+    #[clap(short, long, value_parser)]
+    output: Option<PathBuf>,
+
+    /// Render a single image and exit without opening a window.
+    /// Writes to `render.png` unless `--output` is given
+    #[clap(long)]
+    headless: bool,
+
+    /// Also write depth, normal, albedo, object ID and shadow buffers next to the output
+    #[clap(long)]
+    aovs: bool,
+
+    /// Filter the noise out of the rendered image, guided by its normals, albedo and depth.
+    /// Uses the scene's denoiser settings if it has any
+    #[clap(long)]
+    denoise: bool,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut args = Args::parse();
+fn main() {
+    if let Err(e) = run(Args::parse()) {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
 
+fn run(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
     if !args.scene.exists() {
-        let mut scene_example = [
+        let mut materials = Materials::new();
+        materials.insert("floor", Material::diffuse(Rgb([0.0, 0.5, 1.0])));
+        materials.insert("yellow", Material::diffuse(Rgb([1.0, 1.0, 0.0])));
+        materials.insert(
+            "chrome",
+            Material {
+                reflectivity: 0.8,
+                metalness: 1.0,
+                ..Material::diffuse(Rgb([0.9, 0.9, 0.9]))
+            },
+        );
+        materials.insert(
+            "glass",
+            Material {
+                transparency: 1.0,
+                ior: 1.5,
+                ..Material::diffuse(Rgb([1.0, 1.0, 1.0]))
+            },
+        );
+        let mut objects = vec![
             Shape::new_plane([0.0, 0.0, 1.0], [0.0, 0.0, 1.0]),
             Shape::new_sphere([0.0, 0.0, 1.0], 0.1),
             Shape::new_sphere([1.0, 0.0, 1.0], 0.1),
+            Shape::new_sphere([-0.3, 0.0, 0.8], 0.1),
+        ];
+        objects[0].set_material("floor".to_string());
+        objects[1].set_material("yellow".to_string());
+        objects[2].set_material("chrome".to_string());
+        objects[3].set_material("glass".to_string());
+        let lights = vec![
+            Light::point(Vector::new(0.0, -1.0, 0.0), WHITE, 1.0),
+            Light::ambient(WHITE, 0.05),
         ];
-        scene_example[0].set_color(Rgb([0.0, 0.5, 1.0]));
-        scene_example[1].set_color(Rgb([1.0, 1.0, 0.0]));
-        scene_example[2].set_color(Rgb([1.0, 0.0, 1.0]));
+        let mut scene_example = Scene::default();
+        scene_example.objects = objects;
+        scene_example.materials = materials;
+        scene_example.lights = lights;
         let scene_example = serde_json::to_string_pretty(&scene_example)?;
         std::fs::write(&args.scene, scene_example)?;
         println!("Created example scene at {}", args.scene.display());
@@ -115,10 +278,46 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let file = std::fs::OpenOptions::new()
         .read(true)
-        .open(args.scene)
-        .unwrap();
-    let objects: Vec<Shape> = serde_json::from_reader(file).unwrap();
+        .open(&args.scene)
+        .map_err(|e| format!("Failed to open {}: {e}", args.scene.display()))?;
+    let mut scene: Scene = serde_json::from_reader(file)
+        .map_err(|e| format!("Failed to parse {}: {e}", args.scene.display()))?;
+    let base_dir = args.scene.parent().unwrap_or_else(|| Path::new("."));
+    scene.load(base_dir)?;
+    if let Some(max_depth) = args.max_depth {
+        scene.settings.max_depth = max_depth;
+    }
+    if let Some(integrator) = args.integrator {
+        scene.settings.integrator = integrator;
+    }
+    if let Some(samples) = args.samples {
+        scene.settings.samples = samples;
+    }
+    if let Some(sampler) = args.sampler {
+        scene.settings.sampler = sampler;
+    }
+    if let Some(exposure) = args.exposure {
+        scene.settings.exposure = exposure;
+    }
+    if let Some(tonemap) = args.tonemap {
+        scene.settings.tonemap = tonemap;
+    }
+    if args.denoise {
+        scene.settings.denoise = Some(scene.settings.denoise.unwrap_or_default());
+    }
+    if let Some(threshold) = args.adaptive {
+        scene.settings.adaptive = Some(AdaptiveSampling {
+            threshold,
+            ..scene.settings.adaptive.unwrap_or_default()
+        });
+    }
 
+    if args.headless || args.output.is_some() {
+        let output = args.output.unwrap_or_else(|| PathBuf::from("render.png"));
+        file_render(args.width, args.height, &scene, &output, args.aovs)?;
+        println!("Rendered {}", output.display());
+        return Ok(());
+    }
 
     let event_loop = glutin::event_loop::EventLoop::new();
     let wb = glutin::window::WindowBuilder::new()
@@ -129,7 +328,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let vertex_buffer = glium::VertexBuffer::new(&display, &window::RECT).unwrap();
     let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
-    
+
     let program = glium::Program::from_source(
         &display,
         include_str!("vertex.glsl"),
@@ -138,12 +337,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     )
     .unwrap();
 
-    file_render(args.width, args.height, &objects);
-
-    let mut texture = render_texture(args.width, args.height, &display, &objects);
+    let mut integrator = scene.settings.integrator.build(&scene);
+    let mut accumulator = Accumulator::new(args.width, args.height);
+    accumulator.add_pass(&scene, integrator.as_ref());
+    let mut texture = upload_texture(&display, accumulator.image(), &scene.settings);
+    let mut controls = CameraControls::new();
+    let mut last_update = Instant::now();
+    println!("{}", controls::HELP);
     event_loop.run(move |ev, _, control_flow| {
-        let next_frame_time =
-            std::time::Instant::now() + std::time::Duration::from_nanos(16_666_667);
+        let next_frame_time = Instant::now() + std::time::Duration::from_nanos(16_666_667);
         control_flow.set_wait_until(next_frame_time);
         /* control_flow.set_poll(); */
 
@@ -158,14 +360,38 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     args.width = size.width;
                     args.height = size.height;
                 }
-                _ => return,
+                event => {
+                    if let Some(Action::SaveCamera) = controls.handle_event(&event) {
+                        match save_camera(&args.scene, &scene.camera) {
+                            Ok(()) => println!("Saved camera to {}", args.scene.display()),
+                            Err(e) => eprintln!("Error: {e}"),
+                        }
+                    }
+                    return;
+                }
             },
             glutin::event::Event::NewEvents(_) => {
-                texture = render_texture(args.width, args.height, &display, &objects);
-            }
-            glutin::event::Event::RedrawRequested(_) => {
-                texture = render_texture(args.width, args.height, &display, &objects);
+                let now = Instant::now();
+                let dt = now.duration_since(last_update).as_secs_f64();
+                last_update = now;
+                if let Some(camera) = controls.update(&scene.camera, dt) {
+                    scene.camera = camera;
+                    accumulator.reset();
+                    // Integrators may be fitted to the view, like the depth range
+                    integrator = scene.settings.integrator.build(&scene);
+                }
+                // Keep refining the same image until the view changes
+                if accumulator.dimensions() != (args.width, args.height) {
+                    accumulator = Accumulator::new(args.width, args.height);
+                }
+                accumulator.add_pass(&scene, integrator.as_ref());
+                texture = upload_texture(&display, accumulator.image(), &scene.settings);
+                display.gl_window().window().set_title(&format!(
+                    "Raytracer ({} samples per pixel)",
+                    accumulator.passes() * scene.settings.samples.max(1)
+                ));
             }
+            glutin::event::Event::RedrawRequested(_) => {}
             _ => return,
         }
 
@@ -174,7 +400,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         target
             .draw(
                 &vertex_buffer,
-                &indices,
+                indices,
                 &program,
                 &uniform! {
                     tex: &texture